@@ -1,23 +1,268 @@
 use itertools::Itertools;
 use quick_error::quick_error;
 use rand::Rng;
-use std::collections::BTreeSet;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
 use std::{fmt, iter};
 
+use crate::gamestate::active::GameRng;
 use crate::gamestate::players::{PName, Player};
 use crate::gamestate::power::PowerType;
+use crate::gamestate::rules::{Rules, TieRule};
 use crate::gamestate::teams::{TName, TeamsByName};
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Attack {
     attackers: NamedCombatants,
     defenders: NamedCombatants,
+    // How the attack was built, for the event log. Game states don't need
+    // this, so it isn't saved with them.
+    #[serde(skip)]
+    steps: Vec<AttackStep>,
 }
 
-#[derive(Debug)]
+// The choices made while building an attack, in order
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum AttackStep {
+    Declared {
+        attacker: PName,
+        defender: PName,
+        def_power: PowerType,
+        for_team: TName,
+    },
+    Intercepted {
+        protector: PName,
+    },
+    // A new defense color, e.g. for a protector who took the defender's place
+    DefenseColorChosen(PowerType),
+    Joined {
+        player: PName,
+        side: Side,
+        power: PowerType,
+    },
+    AttackColorRolled(PowerType),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Side {
+    Attack,
+    Defense,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(from = "SavedOutcome")]
 pub struct AttackOutcome {
     attack: Attack,
     /* XXX TEMP pub */ pub new_state: TeamsByName,
+    deaths: Vec<PName>,
+    side_switch: Option<SideSwitch>,
+    // Not recorded in saves from before tie rules
+    report: Option<AttackReport>,
+}
+
+// Saves from before reports existed only recorded who won. They become
+// reports with nothing else known: no strengths, honor or losses.
+#[derive(Deserialize)]
+struct SavedOutcome {
+    attack: Attack,
+    new_state: TeamsByName,
+    deaths: Vec<PName>,
+    #[serde(default)]
+    side_switch: Option<SideSwitch>,
+    #[serde(default)]
+    report: Option<AttackReport>,
+    #[serde(default)]
+    result: Option<AttackResult>,
+}
+
+impl From<SavedOutcome> for AttackOutcome {
+    fn from(saved: SavedOutcome) -> Self {
+        let report = saved.report.or_else(|| {
+            saved.result.map(|result| AttackReport {
+                result,
+                attack_strength: 0,
+                defense_strength: 0,
+                attack_bonus: 0,
+                defense_bonus: 0,
+                honor: Vec::new(),
+                losses: Vec::new(),
+                ended_alliance: None,
+            })
+        });
+        AttackOutcome {
+            attack: saved.attack,
+            new_state: saved.new_state,
+            deaths: saved.deaths,
+            side_switch: saved.side_switch,
+            report,
+        }
+    }
+}
+
+// What happened in an attack, so callers don't need to compare game states
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AttackReport {
+    pub result: AttackResult,
+    // Neither strength includes the color advantage.
+    pub attack_strength: i16,
+    pub defense_strength: i16,
+    pub attack_bonus: i16,
+    // Older reports folded any defense advantage into a negative
+    // `attack_bonus`.
+    #[serde(default)]
+    pub defense_bonus: i16,
+    // Empty in a stalemate. Otherwise the winning side's team comes first,
+    // followed by any teams it shares with (see `HonorSharing`).
+    #[serde(deserialize_with = "awards")]
+    pub honor: Vec<HonorAward>,
+    // In the order the tokens were discarded
+    pub losses: Vec<TokenLoss>,
+    // Set if the attack was made on an ally
+    #[serde(default)]
+    pub ended_alliance: Option<(TName, TName)>,
+}
+
+// Older reports have at most one award.
+fn awards<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<HonorAward>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Awards {
+        One(HonorAward),
+        Shared(Vec<HonorAward>),
+    }
+    Ok(match Option::<Awards>::deserialize(deserializer)? {
+        None => Vec::new(),
+        Some(Awards::One(award)) => vec![award],
+        Some(Awards::Shared(awards)) => awards,
+    })
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HonorAward {
+    pub team: TName,
+    pub honor: i16,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TokenLoss {
+    pub player: PName,
+    pub discarded: PowerType,
+    // With wounds, the token is only discarded once it has too many; until
+    // then this is the number it has.
+    #[serde(default)]
+    pub wounds: Option<u8>,
+    pub died: bool,
+}
+
+impl fmt::Display for AttackReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{}: attack {} (color advantage {:+}) against defense {} (color advantage {:+})",
+            self.result,
+            self.attack_strength,
+            self.attack_bonus,
+            self.defense_strength,
+            self.defense_bonus
+        )?;
+        if let Some((a, b)) = &self.ended_alliance {
+            writeln!(f, "The alliance between {} and {} is broken", a.0, b.0)?;
+        }
+        for award in &self.honor {
+            writeln!(f, "Team {} gains {} honor", award.team.0, award.honor)?;
+        }
+        for loss in &self.losses {
+            match loss.wounds {
+                Some(wounds) => write!(
+                    f,
+                    "{}'s {} token is wounded (wounds: {})",
+                    loss.player, loss.discarded, wounds
+                )?,
+                None => write!(f, "{} discards {}", loss.player, loss.discarded)?,
+            }
+            if loss.died {
+                write!(f, " and dies")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AttackResult {
+    AttackerWon,
+    DefenderWon,
+    // Nobody gains honor; see `TieRule`.
+    Stalemate,
+}
+
+impl fmt::Display for AttackResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AttackResult::AttackerWon => write!(f, "The attackers won"),
+            AttackResult::DefenderWon => write!(f, "The defenders won"),
+            AttackResult::Stalemate => write!(f, "Stalemate"),
+        }
+    }
+}
+
+impl AttackOutcome {
+    pub fn attack(&self) -> &Attack {
+        &self.attack
+    }
+
+    // Players killed by this attack
+    pub fn deaths(&self) -> &[PName] {
+        &self.deaths
+    }
+
+    pub fn report(&self) -> Option<&AttackReport> {
+        self.report.as_ref()
+    }
+
+    pub fn result(&self) -> Option<AttackResult> {
+        self.report.as_ref().map(|report| report.result)
+    }
+
+    pub(crate) fn take_steps(&mut self) -> Vec<AttackStep> {
+        std::mem::take(&mut self.attack.steps)
+    }
+
+    // Set if the attacker fought for a team other than their own
+    pub fn side_switch(&self) -> Option<&SideSwitch> {
+        self.side_switch.as_ref()
+    }
+}
+
+// An attacker publicly fighting for another team. Everyone sees this.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SideSwitch {
+    pub player: PName,
+    pub own_team: TName,
+    pub fought_for: TName,
+}
+
+impl fmt::Display for AttackOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(switch) = &self.side_switch {
+            writeln!(f, "{}!", switch)?;
+        }
+        match &self.report {
+            Some(report) => write!(f, "{}", report),
+            None => writeln!(f, "Deaths: {}", self.deaths.iter().join(", ")),
+        }
+    }
+}
+
+impl fmt::Display for SideSwitch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} (team {}) fought for team {}",
+            self.player, self.own_team.0, self.fought_for.0
+        )
+    }
 }
 
 impl fmt::Display for Attack {
@@ -30,14 +275,71 @@ impl fmt::Display for Attack {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(from = "SavedCombatants")]
 struct NamedCombatants {
     primary: PName,
-    assists: BTreeSet<PName>,
+    // Each assist commits (and loses) one token of their choosing.
+    assists: BTreeMap<PName, PowerType>,
+    power_type: PowerType,
+    for_team: TName,
+}
+
+impl NamedCombatants {
+    // The color committed by each combatant, starting with the primary
+    fn colors(&self) -> impl Iterator<Item = PowerType> + '_ {
+        iter::once(self.power_type).chain(self.assists.values().copied())
+    }
+
+    // The strength each combatant commits, with the team it counts for when
+    // honor is shared: the side's team for the primary, and their own team for
+    // each assist
+    fn contributions(&self, state: &TeamsByName) -> Vec<(TName, i16)> {
+        let primary = state.player_data(&self.primary).strength(self.power_type);
+        iter::once((self.for_team.clone(), primary))
+            .chain(self.assists.iter().map(|(name, &ptype)| {
+                let assist = state.player_data(name);
+                (TName(assist.team.clone()), assist.strength(ptype))
+            }))
+            .map(|(team, strength)| (team, i16::from(strength)))
+            .collect()
+    }
+}
+
+// Older saves list assists without colors; they fought with the primary's.
+#[derive(Deserialize)]
+struct SavedCombatants {
+    primary: PName,
+    assists: SavedAssists,
     power_type: PowerType,
     for_team: TName,
 }
 
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SavedAssists {
+    WithColors(BTreeMap<PName, PowerType>),
+    Names(BTreeSet<PName>),
+}
+
+impl From<SavedCombatants> for NamedCombatants {
+    fn from(saved: SavedCombatants) -> Self {
+        let assists = match saved.assists {
+            SavedAssists::WithColors(assists) => assists,
+            SavedAssists::Names(names) => names
+                .into_iter()
+                .map(|name| (name, saved.power_type))
+                .collect(),
+        };
+        NamedCombatants {
+            primary: saved.primary,
+            assists,
+            power_type: saved.power_type,
+            for_team: saved.for_team,
+        }
+    }
+}
+
 impl fmt::Display for NamedCombatants {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         writeln!(
@@ -46,7 +348,10 @@ impl fmt::Display for NamedCombatants {
             self.primary,
             self.power_type,
             self.for_team.0,
-            self.assists.iter().join(", ")
+            self.assists
+                .iter()
+                .map(|(name, ptype)| format!("{} ({})", name, ptype))
+                .join(", ")
         )
     }
 }
@@ -54,7 +359,7 @@ impl fmt::Display for NamedCombatants {
 #[derive(Debug)]
 struct CombatantRefs<'a> {
     primary: &'a mut Player,
-    assists: Vec<&'a mut Player>,
+    assists: Vec<(&'a mut Player, PowerType)>,
     power_type: PowerType,
 }
 
@@ -63,7 +368,7 @@ impl<'a> CombatantRefs<'a> {
         self
             .assists
             .iter()
-            .map(|a| a.strength(self.power_type))
+            .map(|(a, ptype)| a.strength(*ptype))
             .chain(iter::once(self.primary.strength(self.power_type)))
             // Cast prior to adding, to support overflow from i8
             .map(|a| a as i16)
@@ -72,39 +377,130 @@ impl<'a> CombatantRefs<'a> {
 }
 
 impl Attack {
-    pub fn outcome(self, initial_state: &TeamsByName) -> AttackOutcome {
+    pub fn outcome(self, initial_state: &TeamsByName, rules: &Rules) -> AttackOutcome {
+        let own_team = &initial_state.player_data(&self.attackers.primary).team;
+        let side_switch = (*own_team != self.attackers.for_team.0).then(|| SideSwitch {
+            player: self.attackers.primary.clone(),
+            own_team: TName(own_team.clone()),
+            fought_for: self.attackers.for_team.clone(),
+        });
         let mut new_state = initial_state.clone();
+        // Attacking an ally ends the alliance, however the attack turns out.
+        let ended_alliance = new_state
+            .end_alliance(&self.attackers.for_team.0, &self.defenders.for_team.0)
+            .ok();
         let (attackers, defenders) = self.combatants_by_ref(&mut new_state);
         let attack_strength = attackers.strength();
         let defense_strength = defenders.strength();
-        // TODO DESIGN - should ties, or near-ties, be resolved w/out loss of
-        // power or gain of honor?
-        let attack_succeeds = attack_strength + self.attack_bonus() > defense_strength;
-        let (losers, win_assists, winning_team, honor_won) = if attack_succeeds {
-            (
+        let (attack_bonus, defense_bonus) = self.color_bonuses(rules);
+        let result = rules.ties.resolve(
+            attack_strength + attack_bonus,
+            defense_strength + defense_bonus,
+        );
+        let mut losses = Vec::new();
+        let mut lose_token = |player: &mut Player, ptype| {
+            let (wounds, died_of_wounds) = match rules.wounds {
+                Some(rule) => {
+                    let discarded = player.wound(ptype, rule);
+                    let wounds = (!discarded).then(|| player.wounds(ptype));
+                    (wounds, player.apply_wound_rule(rule))
+                }
+                None => {
+                    player.lose_power(ptype);
+                    (None, false)
+                }
+            };
+            losses.push(TokenLoss {
+                player: player.name.clone(),
+                discarded: ptype,
+                wounds,
+                died: died_of_wounds || player.apply_death_rule(rules.death_rule),
+            });
+        };
+        let (losers, win_assists, winners, honor_won) = match result {
+            AttackResult::AttackerWon => (
                 defenders,
                 attackers.assists,
-                &self.attackers.for_team,
-                defense_strength,
-            )
-        } else {
-            (
+                &self.attackers,
+                rules.honor.honor_won(attack_strength, defense_strength),
+            ),
+            AttackResult::DefenderWon => (
                 attackers,
                 defenders.assists,
-                &self.defenders.for_team,
-                attack_strength,
-            )
+                &self.defenders,
+                rules.honor.honor_won(defense_strength, attack_strength),
+            ),
+            AttackResult::Stalemate => {
+                if let TieRule::BothPrimariesLose = rules.ties {
+                    lose_token(attackers.primary, attackers.power_type);
+                    lose_token(defenders.primary, defenders.power_type);
+                }
+                return self.resolved(
+                    new_state,
+                    side_switch,
+                    AttackReport {
+                        result,
+                        attack_strength,
+                        defense_strength,
+                        attack_bonus,
+                        defense_bonus,
+                        honor: Vec::new(),
+                        losses,
+                        ended_alliance,
+                    },
+                );
+            }
         };
         // The primary combatant always loses their token.
-        losers.primary.lose_power(losers.power_type);
         // Assists on *both* sides of the combat lose their tokens.
-        for assist in losers.assists.into_iter().chain(win_assists.into_iter()) {
-            assist.lose_power(losers.power_type);
+        for (player, ptype) in iter::once((losers.primary, losers.power_type))
+            .chain(losers.assists)
+            .chain(win_assists)
+        {
+            lose_token(player, ptype);
+        }
+        let honor = rules.honor_sharing.split(
+            honor_won,
+            &winners.for_team,
+            &winners.contributions(initial_state),
+        );
+        for award in &honor {
+            new_state.gain_honor(&award.team, award.honor);
         }
-        new_state.gain_honor(winning_team, honor_won);
+        self.resolved(
+            new_state,
+            side_switch,
+            AttackReport {
+                result,
+                attack_strength,
+                defense_strength,
+                attack_bonus,
+                defense_bonus,
+                honor,
+                losses,
+                ended_alliance,
+            },
+        )
+    }
+
+    fn resolved(
+        self,
+        new_state: TeamsByName,
+        side_switch: Option<SideSwitch>,
+        report: AttackReport,
+    ) -> AttackOutcome {
+        let deaths = report
+            .losses
+            .iter()
+            .filter(|loss| loss.died)
+            .map(|loss| loss.player.clone())
+            .collect();
         AttackOutcome {
             attack: self,
             new_state,
+            deaths,
+            side_switch,
+            report: Some(report),
         }
     }
     fn combatants_by_ref<'a>(
@@ -118,10 +514,10 @@ impl Attack {
                 primary_attacker = Some(player);
             } else if player.name == self.defenders.primary {
                 primary_defender = Some(player);
-            } else if self.attackers.assists.contains(&player.name) {
-                attacker_assists.push(player);
-            } else if self.defenders.assists.contains(&player.name) {
-                defender_assists.push(player);
+            } else if let Some(&ptype) = self.attackers.assists.get(&player.name) {
+                attacker_assists.push((player, ptype));
+            } else if let Some(&ptype) = self.defenders.assists.get(&player.name) {
+                defender_assists.push((player, ptype));
             }
         }
         (
@@ -173,44 +569,119 @@ impl Attack {
         )
         */
     }
-    fn attack_bonus(&self) -> i16 {
-        self.attackers
-            .power_type
-            .relative_advantage(self.defenders.power_type)
+    // Each side's advantage, counted per combatant against the colors the
+    // other side committed (see `Rules::side_advantage`)
+    fn color_bonuses(&self, rules: &Rules) -> (i16, i16) {
+        let attacking = self.attackers.colors().collect::<Vec<_>>();
+        let defending = self.defenders.colors().collect::<Vec<_>>();
+        (
+            rules.side_advantage(attacking.iter().copied(), &defending),
+            rules.side_advantage(defending.iter().copied(), &attacking),
+        )
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct DeclaredAttack<'a> {
     initial_attacker: PName,
-    attacker_assists: BTreeSet<PName>,
+    attacker_assists: BTreeMap<PName, PowerType>,
     att_team: TName,
     targeted_defender: PName,
-    defender_assists: BTreeSet<PName>,
+    defender_assists: BTreeMap<PName, PowerType>,
     def_team: TName,
     def_power: PowerType,
+    steps: Vec<AttackStep>,
     state: &'a TeamsByName,
 }
 
+// Everything about a declared attack is public knowledge.
 impl<'a> DeclaredAttack<'a> {
+    pub fn initial_attacker(&self) -> &PName {
+        &self.initial_attacker
+    }
+
+    pub fn attacker_assists(&self) -> &BTreeMap<PName, PowerType> {
+        &self.attacker_assists
+    }
+
+    pub fn att_team(&self) -> &TName {
+        &self.att_team
+    }
+
+    pub fn targeted_defender(&self) -> &PName {
+        &self.targeted_defender
+    }
+
+    pub fn defender_assists(&self) -> &BTreeMap<PName, PowerType> {
+        &self.defender_assists
+    }
+
+    pub fn def_team(&self) -> &TName {
+        &self.def_team
+    }
+
+    pub fn def_power(&self) -> PowerType {
+        self.def_power
+    }
+
+    pub(crate) fn state(&self) -> &'a TeamsByName {
+        self.state
+    }
+
+    pub fn is_combatant(&self, name: &PName) -> bool {
+        *name == self.initial_attacker
+            || *name == self.targeted_defender
+            || self.attacker_assists.contains_key(name)
+            || self.defender_assists.contains_key(name)
+    }
+
     // Initiates an attack, returning a closure over the data necessary to perform the next step of the
     // attack.
-    // TODO DESIGN: Let attacker declare which team they're fighting for?
+    // The attacker fights for their own team unless they name another one with
+    // `for_team`; any honor they win goes to that team, and everyone sees the
+    // switch. They can't switch to the defender's team.
     pub fn declare<'g>(
         state: &'g TeamsByName,
         attacker: &str,
         defender: &str,
         def_power: PowerType,
+        for_team: Option<&str>,
     ) -> Result<AddDefender<'g>, InvalidAttackErr> {
-        let (attacker_name, att_team) = state
+        let (attacker_name, own_team) = state
             .player_by_name(attacker)
             .ok_or(InvalidAttackErr::CombatantNotFound)?;
         let (defender_name, def_team) = state
             .player_by_name(defender)
             .ok_or(InvalidAttackErr::CombatantNotFound)?;
+        let att_team = match for_team {
+            Some(team) if team != own_team.0 => {
+                let team = state
+                    .teams()
+                    .map(|(name, _)| name)
+                    .find(|name| name.0 == team)
+                    .cloned()
+                    .ok_or(InvalidAttackErr::TeamNotFound)?;
+                if team == def_team {
+                    return Err(InvalidAttackErr::FightingForDefenders);
+                }
+                team
+            }
+            _ => own_team,
+        };
+        if !state.player_data(&attacker_name).is_alive()
+            || !state.player_data(&defender_name).is_alive()
+        {
+            return Err(InvalidAttackErr::CombatantDead);
+        }
         if !state.player_data(&defender_name).has_power(def_power) {
             return Err(InvalidAttackErr::CombatantMissingPowerType);
         }
+        let declared = AttackStep::Declared {
+            attacker: attacker_name.clone(),
+            defender: defender_name.clone(),
+            def_power,
+            for_team: att_team.clone(),
+        };
         Ok(AddDefender {
             attack: DeclaredAttack {
                 initial_attacker: attacker_name,
@@ -220,6 +691,7 @@ impl<'a> DeclaredAttack<'a> {
                 defender_assists: Default::default(),
                 def_team,
                 def_power,
+                steps: vec![declared],
                 state,
             },
         })
@@ -239,6 +711,7 @@ impl<'a> DeclaredAttack<'a> {
                 power_type: self.def_power,
                 for_team: self.def_team,
             },
+            steps: self.steps,
         }
     }
 }
@@ -256,15 +729,16 @@ Defenders (power: {}): representing team {}; combatants:
 {}",
             self.att_team.0,
             self.state.pretty_player(&self.initial_attacker),
-            self.state.pretty_players(self.attacker_assists.iter()),
+            self.state.pretty_players(self.attacker_assists.keys()),
             self.def_power,
             self.def_team.0,
             self.state.pretty_player(&self.targeted_defender),
-            self.state.pretty_players(self.defender_assists.iter()),
+            self.state.pretty_players(self.defender_assists.keys()),
         )
     }
 }
 
+#[derive(Clone)]
 pub struct AddDefender<'a> {
     pub attack: DeclaredAttack<'a>,
 }
@@ -279,29 +753,41 @@ quick_error! {
     #[derive(Debug)]
     pub enum InvalidAttackErr {
         CombatantNotFound {}
+        CombatantDead {}
         CombatantMissingPowerType {}
         DuplicateCombatant {}
         AttackerAlreadyDefending {}
+        NotSwornProtector {}
+        TeamNotFound {}
+        FightingForDefenders {}
     }
 }
 
 impl<'a> AddDefender<'a> {
+    // Joins the defense with the defense color.
     pub fn add(&mut self, name: &str) -> Result<(), InvalidAttackErr> {
+        self.add_with(name, self.attack.def_power)
+    }
+
+    // Joins the defense, sacrificing a token of the given color.
+    pub fn add_with(&mut self, name: &str, ptype: PowerType) -> Result<(), InvalidAttackErr> {
         // TODO warn if defender is on attacker's team?
-        // TODO DESIGN - since assists sacrifice their tokens, should they be permitted to
-        // pick a token to sacrifice?
         if let Some((pname, _)) = self.attack.state.player_by_name(name) {
-            if !self
-                .attack
-                .state
-                .player_data(&pname)
-                .has_power(self.attack.def_power)
-            {
+            if !self.attack.state.player_data(&pname).is_alive() {
+                return Err(InvalidAttackErr::CombatantDead);
+            }
+            if !self.attack.state.player_data(&pname).has_power(ptype) {
                 return Err(InvalidAttackErr::CombatantMissingPowerType);
             }
-            if !self.attack.defender_assists.insert(pname) {
+            if self.attack.is_combatant(&pname) {
                 return Err(InvalidAttackErr::DuplicateCombatant);
             }
+            self.attack.steps.push(AttackStep::Joined {
+                player: pname.clone(),
+                side: Side::Defense,
+                power: ptype,
+            });
+            self.attack.defender_assists.insert(pname, ptype);
             Ok(())
         } else {
             Err(InvalidAttackErr::CombatantNotFound)
@@ -313,15 +799,77 @@ impl<'a> AddDefender<'a> {
         self
     }
 
-    pub fn finalize_defense(self) -> AddAttacker<'a> {
-        let mut rng = rand::thread_rng();
+    // Living players sworn to protect the targeted defender who are able to
+    // take their place, using the defense color already chosen.
+    pub fn sworn_protectors(&self) -> Vec<PName> {
+        let attack = &self.attack;
+        attack
+            .state
+            .players()
+            .filter(|p| p.ward() == Some(&attack.targeted_defender))
+            .filter(|p| p.is_alive() && p.has_power(attack.def_power))
+            .filter(|p| p.name != attack.initial_attacker)
+            .map(|p| p.name.clone())
+            .sorted()
+            .collect()
+    }
+
+    // A sworn protector may sacrifice themselves by taking the targeted
+    // defender's place as the primary defender. The original target is no
+    // longer part of the fight (though they may still join as an assist).
+    pub fn intercept(&mut self, protector: &str) -> Result<(), InvalidAttackErr> {
+        let (pname, _) = self
+            .attack
+            .state
+            .player_by_name(protector)
+            .ok_or(InvalidAttackErr::CombatantNotFound)?;
+        if !self.sworn_protectors().contains(&pname) {
+            return Err(InvalidAttackErr::NotSwornProtector);
+        }
+        self.replace_target(pname);
+        Ok(())
+    }
+
+    // Changes the defense color, which the targeted defender must hold.
+    // Assists who have already joined keep the color they committed.
+    pub fn choose_def_power(&mut self, def_power: PowerType) -> Result<(), InvalidAttackErr> {
+        let defender = self.attack.state.player_data(&self.attack.targeted_defender);
+        if !defender.has_power(def_power) {
+            return Err(InvalidAttackErr::CombatantMissingPowerType);
+        }
+        self.attack
+            .steps
+            .push(AttackStep::DefenseColorChosen(def_power));
+        self.attack.def_power = def_power;
+        Ok(())
+    }
+
+    fn replace_target(&mut self, protector: PName) {
+        self.attack.steps.push(AttackStep::Intercepted {
+            protector: protector.clone(),
+        });
+        self.attack.defender_assists.remove(&protector);
+        self.attack.targeted_defender = protector;
+    }
+
+    pub fn finalize_defense(self, rng: &mut GameRng) -> AddAttacker<'a> {
+        let att_power = rng.gen();
+        self.finalize_defense_with(att_power)
+    }
+
+    // Skips the roll; only for replaying a logged game.
+    pub(crate) fn finalize_defense_with(mut self, att_power: PowerType) -> AddAttacker<'a> {
+        self.attack
+            .steps
+            .push(AttackStep::AttackColorRolled(att_power));
         AddAttacker {
             attack: self.attack,
-            att_power: rng.gen(),
+            att_power,
         }
     }
 }
 
+#[derive(Clone)]
 pub struct AddAttacker<'a> {
     pub attack: DeclaredAttack<'a>,
     att_power: PowerType,
@@ -336,25 +884,39 @@ impl<'a> fmt::Display for AddAttacker<'a> {
 }
 
 impl<'a> AddAttacker<'a> {
+    pub fn att_power(&self) -> PowerType {
+        self.att_power
+    }
+
+    // Joins the offense with the attack color.
     pub fn add(&mut self, name: &str) -> Result<(), InvalidAttackErr> {
+        self.add_with(name, self.att_power)
+    }
+
+    // Joins the offense, sacrificing a token of the given color.
+    pub fn add_with(&mut self, name: &str, ptype: PowerType) -> Result<(), InvalidAttackErr> {
         // TODO warn if attacker is on defender's team?
         if let Some((pname, _)) = self.attack.state.player_by_name(name) {
-            if !self
-                .attack
-                .state
-                .player_data(&pname)
-                .has_power(self.attack.def_power)
-            {
+            if !self.attack.state.player_data(&pname).is_alive() {
+                return Err(InvalidAttackErr::CombatantDead);
+            }
+            if !self.attack.state.player_data(&pname).has_power(ptype) {
                 return Err(InvalidAttackErr::CombatantMissingPowerType);
             }
             if pname == self.attack.targeted_defender
-                || self.attack.defender_assists.contains(&pname)
+                || self.attack.defender_assists.contains_key(&pname)
             {
                 return Err(InvalidAttackErr::AttackerAlreadyDefending);
             }
-            if !self.attack.attacker_assists.insert(pname) {
+            if self.attack.is_combatant(&pname) {
                 return Err(InvalidAttackErr::DuplicateCombatant);
             }
+            self.attack.steps.push(AttackStep::Joined {
+                player: pname.clone(),
+                side: Side::Attack,
+                power: ptype,
+            });
+            self.attack.attacker_assists.insert(pname, ptype);
             Ok(())
         } else {
             Err(InvalidAttackErr::CombatantNotFound)
@@ -370,3 +932,328 @@ impl<'a> AddAttacker<'a> {
         self.attack.finalize(self.att_power)
     }
 }
+
+#[test]
+fn dead_players_cannot_fight() {
+    use crate::gamestate::players::DeathRule;
+
+    let mut game = crate::gamestate::builder::test_game(0, Default::default());
+    let (victim, _) = game.player_by_name("Kyle").unwrap();
+    let victim_team = game.player_data(&victim).team.clone();
+    let opponent = game
+        .players()
+        .find(|p| p.team != victim_team)
+        .unwrap()
+        .name
+        .to_string();
+    let ally = game
+        .players()
+        .find(|p| p.team == victim_team && p.name != victim)
+        .unwrap()
+        .name
+        .to_string();
+
+    let player = game.player_mut(&victim);
+    player.lose_power(PowerType::Red);
+    assert!(!player.apply_death_rule(DeathRule::TokensDiscarded(2)));
+    player.lose_power(PowerType::Blue);
+    assert!(player.apply_death_rule(DeathRule::TokensDiscarded(2)));
+    assert!(!player.is_alive());
+
+    assert!(matches!(
+        DeclaredAttack::declare(
+            game.current_state(),
+            "Kyle",
+            &opponent,
+            PowerType::Red,
+            None
+        ),
+        Err(InvalidAttackErr::CombatantDead)
+    ));
+    assert!(matches!(
+        DeclaredAttack::declare(
+            game.current_state(),
+            &opponent,
+            "Kyle",
+            PowerType::Green,
+            None
+        ),
+        Err(InvalidAttackErr::CombatantDead)
+    ));
+    let mut defenders =
+        DeclaredAttack::declare(game.current_state(), &opponent, &ally, PowerType::Red, None)
+            .unwrap();
+    assert!(matches!(
+        defenders.add("Kyle"),
+        Err(InvalidAttackErr::CombatantDead)
+    ));
+}
+
+#[test]
+fn protector_takes_defenders_place() {
+    let game = crate::gamestate::builder::test_game(0, Default::default());
+    let (target, _) = game.player_by_name("Kyle").unwrap();
+    let protector = game
+        .players()
+        .find(|p| p.ward() == Some(&target))
+        .unwrap()
+        .name
+        .clone();
+    let attacker = game
+        .players()
+        .find(|p| p.name != target && p.name != protector)
+        .unwrap()
+        .name
+        .to_string();
+
+    let mut defense = DeclaredAttack::declare(
+        game.current_state(),
+        &attacker,
+        "Kyle",
+        PowerType::Red,
+        None,
+    )
+    .unwrap();
+    assert!(matches!(
+        defense.intercept(&attacker),
+        Err(InvalidAttackErr::NotSwornProtector)
+    ));
+    assert_eq!(defense.sworn_protectors(), vec![protector.clone()]);
+    defense.intercept(protector.as_str()).unwrap();
+    assert_eq!(defense.attack.targeted_defender(), &protector);
+}
+
+#[test]
+fn attacker_may_fight_for_another_team() {
+    let mut game = crate::gamestate::builder::test_setup(0, Default::default())
+        .add_team_or_panic("Jutes")
+        .add_player_or_panic("Luna")
+        .add_player_or_panic("Rosie")
+        .add_player_or_panic("Ida")
+        .finalize()
+        .unwrap();
+    let team_of = |game: &crate::gamestate::active::ActiveGame, name: &str| {
+        let (pname, team) = game.player_by_name(name).unwrap();
+        (pname, team.0)
+    };
+    let (_, kyle_team) = team_of(&game, "Kyle");
+    let defender = game
+        .players()
+        .find(|p| p.team != kyle_team)
+        .unwrap()
+        .name
+        .to_string();
+    let (_, def_team) = team_of(&game, &defender);
+    let third_team = ["Geats", "Danes", "Jutes"]
+        .into_iter()
+        .find(|&t| t != kyle_team && t != def_team)
+        .unwrap();
+
+    let (state, rng) = game.state_and_rng();
+    assert!(matches!(
+        DeclaredAttack::declare(state, "Kyle", &defender, PowerType::Red, Some(&def_team)),
+        Err(InvalidAttackErr::FightingForDefenders)
+    ));
+    assert!(matches!(
+        DeclaredAttack::declare(state, "Kyle", &defender, PowerType::Red, Some("Picts")),
+        Err(InvalidAttackErr::TeamNotFound)
+    ));
+    let attack =
+        DeclaredAttack::declare(state, "Kyle", &defender, PowerType::Red, Some(third_team))
+            .unwrap()
+            .finalize_defense(rng)
+            .finalize_offense();
+    let outcome = game.preview(attack);
+    let switch = outcome.side_switch().unwrap();
+    assert_eq!(switch.own_team.0, kyle_team);
+    assert_eq!(switch.fought_for.0, third_team);
+    // Only the team Kyle fought for can have gained honor.
+    let honor = |team: &str| {
+        outcome
+            .new_state
+            .teams()
+            .find(|(name, _)| name.0 == team)
+            .unwrap()
+            .1
+            .honor()
+    };
+    assert_eq!(honor(&kyle_team), 0);
+    game.apply_attack_outcome(outcome);
+    assert_eq!(game.side_switches().count(), 1);
+}
+
+#[test]
+fn assists_commit_their_own_colors() {
+    let mut game = crate::gamestate::builder::test_game(0, Default::default());
+    let (kyle, kyle_team) = game.player_by_name("Kyle").unwrap();
+    let mut others = game
+        .players()
+        .filter(|p| p.name != kyle)
+        .map(|p| (p.name.to_string(), p.team != kyle_team.0))
+        .collect::<Vec<_>>();
+    others.sort_by_key(|&(_, opponent)| !opponent);
+    let defender = others.remove(0).0;
+    let (def_assist, att_assist) = (others[0].0.clone(), others[1].0.clone());
+    let (att_assist_name, _) = game.player_by_name(&att_assist).unwrap();
+    game.player_mut(&att_assist_name)
+        .lose_power(PowerType::Blue);
+
+    let (state, rng) = game.state_and_rng();
+    let mut defense =
+        DeclaredAttack::declare(state, "Kyle", &defender, PowerType::Red, None).unwrap();
+    defense.add_with(&def_assist, PowerType::Green).unwrap();
+    let mut offense = defense.finalize_defense(rng);
+    assert!(matches!(
+        offense.add_with(&att_assist, PowerType::Blue),
+        Err(InvalidAttackErr::CombatantMissingPowerType)
+    ));
+    offense.add_with(&att_assist, PowerType::Green).unwrap();
+    let attack = offense.finalize_offense();
+    let outcome = game.preview(attack);
+
+    // Assists on both sides lose only the token they committed.
+    for (name, committed) in [
+        (def_assist, PowerType::Green),
+        (att_assist, PowerType::Green),
+    ] {
+        let (pname, _) = outcome.new_state.player_by_name(&name).unwrap();
+        let player = outcome.new_state.player_data(&pname);
+        assert!(!player.has_power(committed));
+        assert!(player.has_power(PowerType::Red));
+    }
+}
+
+#[test]
+fn report_describes_the_outcome() {
+    let mut game = crate::gamestate::builder::test_game(3, Default::default());
+    let (kyle, kyle_team) = game.player_by_name("Kyle").unwrap();
+    let names = |opponents: bool| {
+        game.players()
+            .filter(|p| p.name != kyle && (p.team != kyle_team.0) == opponents)
+            .map(|p| p.name.to_string())
+            .collect::<Vec<_>>()
+    };
+    let (allies, opponents) = (names(false), names(true));
+
+    let (state, rng) = game.state_and_rng();
+    let attack = DeclaredAttack::declare(state, "Kyle", &opponents[0], PowerType::Blue, None)
+        .unwrap()
+        .add_or_panic(&opponents[1])
+        .finalize_defense(rng)
+        .add_or_panic(&allies[0])
+        .finalize_offense();
+    let outcome = game.preview(attack);
+    let report = outcome.report().unwrap();
+
+    let attack_total = report.attack_strength + report.attack_bonus;
+    let defense_total = report.defense_strength + report.defense_bonus;
+    assert_eq!(
+        report.result == AttackResult::AttackerWon,
+        attack_total > defense_total
+    );
+    // The losing primary and both assists discard a token.
+    assert_eq!(report.losses.len(), 3);
+    for loss in &report.losses {
+        assert!(!outcome
+            .new_state
+            .player_data(&loss.player)
+            .has_power(loss.discarded));
+    }
+    let award = report.honor.first().unwrap();
+    let honor = outcome
+        .new_state
+        .teams()
+        .find(|(name, _)| **name == award.team)
+        .unwrap()
+        .1
+        .honor();
+    assert_eq!(honor, award.honor);
+}
+
+#[test]
+fn wounds_weaken_tokens_before_discarding_them() {
+    use crate::gamestate::players::WoundRule;
+    use crate::gamestate::rules::Rules;
+
+    let wounds = WoundRule {
+        penalty: 1,
+        discard_at: 2,
+        fatal_at: Some(3),
+    };
+    let mut game = crate::gamestate::builder::test_game(
+        3,
+        Rules {
+            wounds: Some(wounds),
+            ..Default::default()
+        },
+    );
+    let (kyle, kyle_team) = game.player_by_name("Kyle").unwrap();
+    let opponent = game
+        .players()
+        .find(|p| p.team != kyle_team.0)
+        .unwrap()
+        .name
+        .to_string();
+
+    let (state, rng) = game.state_and_rng();
+    let attack = DeclaredAttack::declare(state, "Kyle", &opponent, PowerType::Blue, None)
+        .unwrap()
+        .finalize_defense(rng)
+        .finalize_offense();
+    let outcome = game.preview(attack);
+    let report = outcome.report().unwrap();
+    // Only the losing primary is hit, and one wound doesn't discard a token.
+    assert_eq!(report.losses.len(), 1);
+    let loss = &report.losses[0];
+    assert_eq!(loss.wounds, Some(1));
+    assert!(!loss.died);
+    let loser = outcome.new_state.player_data(&loss.player);
+    assert!(loser.has_power(loss.discarded));
+    assert!(loser.to_string().contains("wounds"));
+
+    let player = game.player_mut(&kyle);
+    let strength = player.strength(PowerType::Red);
+    assert!(!player.wound(PowerType::Red, wounds));
+    assert_eq!(player.strength(PowerType::Red), (strength - 1).max(0));
+    assert!(player.wound(PowerType::Red, wounds));
+    assert!(!player.has_power(PowerType::Red));
+    assert!(!player.apply_wound_rule(wounds));
+    player.wound(PowerType::Green, wounds);
+    assert!(player.apply_wound_rule(wounds));
+}
+
+#[test]
+fn both_sides_gain_color_advantage_per_combatant() {
+    let game = crate::gamestate::builder::test_game(1, Default::default());
+    let (kyle, kyle_team) = game.player_by_name("Kyle").unwrap();
+    let names = |opponents: bool| {
+        game.players()
+            .filter(|p| p.name != kyle && (p.team != kyle_team.0) == opponents)
+            .map(|p| p.name.to_string())
+            .collect::<Vec<_>>()
+    };
+    let (allies, opponents) = (names(false), names(true));
+
+    // Red beats Green, Green beats Blue, and Blue beats Red. The attackers
+    // commit Red and Green against Blue and Red: only Green is favored. On
+    // defense both Blue and Red are.
+    let state = game.current_state();
+    let mut defense =
+        DeclaredAttack::declare(state, "Kyle", &opponents[0], PowerType::Blue, None).unwrap();
+    defense.add_with(&opponents[1], PowerType::Red).unwrap();
+    let mut offense = defense.finalize_defense_with(PowerType::Red);
+    offense.add_with(&allies[0], PowerType::Green).unwrap();
+    let outcome = game.preview(offense.finalize_offense());
+    let report = outcome.report().unwrap();
+    assert_eq!((report.attack_bonus, report.defense_bonus), (2, 4));
+
+    // One on one, only the favored color gains anything.
+    let state = game.current_state();
+    let attack = DeclaredAttack::declare(state, "Kyle", &opponents[0], PowerType::Blue, None)
+        .unwrap()
+        .finalize_defense_with(PowerType::Green)
+        .finalize_offense();
+    let outcome = game.preview(attack);
+    let report = outcome.report().unwrap();
+    assert_eq!((report.attack_bonus, report.defense_bonus), (2, 0));
+}
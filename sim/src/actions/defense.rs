@@ -0,0 +1,161 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::iter;
+
+use crate::actions::attack::{AddDefender, AttackResult};
+use crate::actions::odds;
+use crate::gamestate::players::PName;
+use crate::gamestate::power::PowerType;
+use crate::gamestate::rules::Rules;
+
+// How a defense color is likely to turn out, over every possible attack color
+#[derive(Debug)]
+pub struct DefenseOption {
+    pub power: PowerType,
+    // Chance that the targeted defender lives through the attack
+    pub survival: f64,
+    // Chance that the attack fails
+    pub holds: f64,
+    // Expected number of tokens discarded (or wounded) on the defending side
+    pub expected_losses: f64,
+    strength: i8,
+}
+
+impl fmt::Display for DefenseOption {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}): survive {:.0}%, hold {:.0}%, lose {:.1} tokens on average",
+            self.power,
+            self.strength,
+            100.0 * self.survival,
+            100.0 * self.holds,
+            self.expected_losses
+        )
+    }
+}
+
+// Ranks the colors the targeted defender of a declared attack holds, best
+// first: the likeliest to survive, then the fewest expected losses, then the
+// weakest token, since it costs the least to lose. The attack is taken as it
+// stands, including the team the attacker fights for, any protector who has
+// taken the defender's place, and any defender assists, who keep the colors
+// they committed. Attacker assists who can't commit the rolled color are left
+// out.
+pub fn rank_defenses(
+    declared: &AddDefender,
+    rules: &Rules,
+    attack_assists: &[PName],
+) -> Vec<DefenseOption> {
+    let defender = declared.attack.targeted_defender();
+    let mut options = PowerType::ALL
+        .iter()
+        .filter_map(|&power| {
+            let mut defense = declared.clone();
+            defense.choose_def_power(power).ok()?;
+            let defenders = iter::once(defender)
+                .chain(defense.attack.defender_assists().keys())
+                .cloned()
+                .collect::<Vec<_>>();
+            let (mut survived, mut held, mut losses) = (0, 0, 0);
+            for &roll in PowerType::ALL.iter() {
+                let mut offense = defense.clone().finalize_defense_with(roll);
+                for assist in attack_assists {
+                    let _ = offense.add(assist.as_str());
+                }
+                let (_, report) = odds::resolve(offense, rules);
+                if !report
+                    .losses
+                    .iter()
+                    .any(|loss| loss.player == *defender && loss.died)
+                {
+                    survived += 1;
+                }
+                if report.result != AttackResult::AttackerWon {
+                    held += 1;
+                }
+                losses += report
+                    .losses
+                    .iter()
+                    .filter(|loss| defenders.contains(&loss.player))
+                    .count();
+            }
+            let rolls = PowerType::ALL.len() as f64;
+            Some(DefenseOption {
+                power,
+                survival: f64::from(survived) / rolls,
+                holds: f64::from(held) / rolls,
+                expected_losses: losses as f64 / rolls,
+                strength: declared
+                    .attack
+                    .state()
+                    .player_data(defender)
+                    .strength(power),
+            })
+        })
+        .collect::<Vec<_>>();
+    options.sort_by(|a, b| {
+        b.survival
+            .partial_cmp(&a.survival)
+            .unwrap_or(Ordering::Equal)
+            .then(
+                a.expected_losses
+                    .partial_cmp(&b.expected_losses)
+                    .unwrap_or(Ordering::Equal),
+            )
+            .then(a.strength.cmp(&b.strength))
+    });
+    options
+}
+
+#[test]
+fn defenses_are_ranked_best_first() {
+    use crate::actions::attack::DeclaredAttack;
+    use crate::gamestate::players::DeathRule;
+
+    let mut game = crate::gamestate::builder::test_game(
+        4,
+        Rules {
+            death_rule: DeathRule::TokensDiscarded(1),
+            ..Default::default()
+        },
+    );
+    let (kyle, kyle_team) = game.player_by_name("Kyle").unwrap();
+    let protector = game
+        .players()
+        .find(|p| p.ward() == Some(&kyle))
+        .unwrap()
+        .name
+        .clone();
+    let attacker = game
+        .players()
+        .find(|p| p.team != kyle_team.0 && p.name != protector)
+        .unwrap()
+        .name
+        .clone();
+    game.player_mut(&kyle).lose_power(PowerType::Green);
+
+    let state = game.current_state();
+    let declared =
+        DeclaredAttack::declare(state, attacker.as_str(), "Kyle", PowerType::Red, None).unwrap();
+    let options = rank_defenses(&declared, game.rules(), &[]);
+    // Only the colors Kyle still holds
+    assert_eq!(options.len(), 2);
+    assert!(options.iter().all(|o| !matches!(o.power, PowerType::Green)));
+    // Any lost fight kills Kyle, so surviving and holding are the same.
+    for option in &options {
+        assert_eq!(option.survival, option.holds);
+        assert!((option.expected_losses + option.holds - 1.0).abs() < 1e-9);
+    }
+    assert!(options[0].survival >= options[1].survival);
+
+    // Once a protector takes Kyle's place, the colors are theirs.
+    let mut declared = declared;
+    declared.intercept(protector.as_str()).unwrap();
+    let options = rank_defenses(&declared, game.rules(), &[]);
+    assert_eq!(options.len(), 3);
+    for option in &options {
+        let strength = state.player_data(&protector).strength(option.power);
+        assert!(option.to_string().contains(&format!("({})", strength)));
+    }
+}
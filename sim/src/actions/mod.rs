@@ -1 +1,3 @@
 pub mod attack;
+pub mod defense;
+pub mod odds;
@@ -0,0 +1,279 @@
+use itertools::Itertools;
+use std::fmt;
+
+use crate::actions::attack::{
+    AddAttacker, AddDefender, AttackReport, AttackResult, DeclaredAttack, Side,
+};
+use crate::gamestate::players::PName;
+use crate::gamestate::power::PowerType;
+use crate::gamestate::rules::Rules;
+
+// How an attack being built would turn out, for each attack color that could
+// still be rolled. Every roll is equally likely.
+#[derive(Debug)]
+pub struct Odds {
+    pub rolls: Vec<(PowerType, AttackReport)>,
+}
+
+impl Odds {
+    fn of_defense(defense: &AddDefender, rules: &Rules) -> Odds {
+        Odds {
+            rolls: PowerType::ALL
+                .iter()
+                .map(|&roll| resolve(defense.clone().finalize_defense_with(roll), rules))
+                .collect(),
+        }
+    }
+
+    fn of_offense(offense: &AddAttacker, rules: &Rules) -> Odds {
+        Odds {
+            rolls: vec![resolve(offense.clone(), rules)],
+        }
+    }
+
+    fn probability(&self, matches: impl Fn(&AttackReport) -> bool) -> f64 {
+        let count = self
+            .rolls
+            .iter()
+            .filter(|(_, report)| matches(report))
+            .count();
+        count as f64 / self.rolls.len() as f64
+    }
+
+    pub fn success(&self) -> f64 {
+        self.probability(|report| report.result == AttackResult::AttackerWon)
+    }
+
+    pub fn expected_honor(&self, side: Side) -> f64 {
+        let winning = match side {
+            Side::Attack => AttackResult::AttackerWon,
+            Side::Defense => AttackResult::DefenderWon,
+        };
+        let total: i16 = self
+            .rolls
+            .iter()
+            .filter(|(_, report)| report.result == winning)
+            .flat_map(|(_, report)| &report.honor)
+            .map(|award| award.honor)
+            .sum();
+        f64::from(total) / self.rolls.len() as f64
+    }
+}
+
+impl fmt::Display for Odds {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "attack succeeds {:.0}% of the time; expected honor: attackers {:.1}, defenders {:.1}",
+            100.0 * self.success(),
+            self.expected_honor(Side::Attack),
+            self.expected_honor(Side::Defense)
+        )?;
+        for (roll, report) in &self.rolls {
+            writeln!(
+                f,
+                "    {} attack: {}, {} to {}",
+                roll,
+                report.result,
+                report.attack_strength + report.attack_bonus,
+                report.defense_strength + report.defense_bonus
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// The odds of an attack as it stands, and how they would change if any one
+// more player joined either side. Joining players commit their side's color.
+#[derive(Debug)]
+pub struct OddsReport {
+    pub odds: Odds,
+    pub joins: Vec<Join>,
+}
+
+#[derive(Debug)]
+pub struct Join {
+    pub player: PName,
+    pub side: Side,
+    pub odds: Odds,
+    // Attack colors the player doesn't hold, so can't join with if rolled.
+    // Those rolls turn out as the attack stands.
+    pub unable: Vec<PowerType>,
+}
+
+impl fmt::Display for OddsReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "As it stands, {}", self.odds)?;
+        for join in &self.joins {
+            let side = match join.side {
+                Side::Attack => "attack",
+                Side::Defense => "defense",
+            };
+            write!(f, "If {} joins the {}", join.player, side)?;
+            if !join.unable.is_empty() {
+                write!(
+                    f,
+                    " (they can't on a {} roll)",
+                    join.unable.iter().join(" or ")
+                )?;
+            }
+            write!(f, ", {}", join.odds)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> AddDefender<'a> {
+    // Attackers can only join once their color is rolled, so those who lack
+    // some colors might only join on some rolls (see `Join::unable`).
+    pub fn odds(&self, rules: &Rules) -> OddsReport {
+        let mut joins = Vec::new();
+        for player in bystanders(&self.attack) {
+            let mut defense = self.clone();
+            if defense.add(player.as_str()).is_ok() {
+                joins.push(Join {
+                    player: player.clone(),
+                    side: Side::Defense,
+                    odds: Odds::of_defense(&defense, rules),
+                    unable: Vec::new(),
+                });
+            }
+            let mut unable = Vec::new();
+            let rolls = PowerType::ALL
+                .iter()
+                .map(|&roll| {
+                    let mut offense = self.clone().finalize_defense_with(roll);
+                    if offense.add(player.as_str()).is_err() {
+                        unable.push(roll);
+                    }
+                    resolve(offense, rules)
+                })
+                .collect();
+            if unable.len() < PowerType::ALL.len() {
+                joins.push(Join {
+                    player,
+                    side: Side::Attack,
+                    odds: Odds { rolls },
+                    unable,
+                });
+            }
+        }
+        OddsReport {
+            odds: Odds::of_defense(self, rules),
+            joins,
+        }
+    }
+}
+
+impl<'a> AddAttacker<'a> {
+    // The defense is final, so only attackers can still join.
+    pub fn odds(&self, rules: &Rules) -> OddsReport {
+        let mut joins = Vec::new();
+        for player in bystanders(&self.attack) {
+            let mut offense = self.clone();
+            if offense.add(player.as_str()).is_ok() {
+                joins.push(Join {
+                    player,
+                    side: Side::Attack,
+                    odds: Odds::of_offense(&offense, rules),
+                    unable: Vec::new(),
+                });
+            }
+        }
+        OddsReport {
+            odds: Odds::of_offense(self, rules),
+            joins,
+        }
+    }
+}
+
+pub(crate) fn resolve(offense: AddAttacker, rules: &Rules) -> (PowerType, AttackReport) {
+    let state = offense.attack.state();
+    let roll = offense.att_power();
+    let outcome = offense.finalize_offense().outcome(state, rules);
+    let report = outcome.report().cloned();
+    (roll, report.expect("New outcomes always have a report"))
+}
+
+// Living players not yet in the fight
+fn bystanders(attack: &DeclaredAttack) -> Vec<PName> {
+    attack
+        .state()
+        .players()
+        .filter(|p| p.is_alive() && !attack.is_combatant(&p.name))
+        .map(|p| p.name.clone())
+        .collect()
+}
+
+#[test]
+fn more_defenders_never_help_the_attack() {
+    let mut game = crate::gamestate::builder::test_game(2, Default::default());
+    let rules = game.rules().clone();
+    let (kyle, kyle_team) = game.player_by_name("Kyle").unwrap();
+    let defender = game
+        .players()
+        .find(|p| p.team != kyle_team.0)
+        .unwrap()
+        .name
+        .to_string();
+
+    let (state, rng) = game.state_and_rng();
+    let defense = DeclaredAttack::declare(state, "Kyle", &defender, PowerType::Red, None).unwrap();
+    let report = defense.odds(&rules);
+    assert_eq!(report.odds.rolls.len(), 3);
+    // Everyone but the two primaries could join one side or the other.
+    assert!(report.joins.len() >= 4);
+    for join in &report.joins {
+        assert!(join.player != kyle);
+        if join.side == Side::Defense {
+            assert!(join.odds.success() <= report.odds.success());
+        }
+    }
+
+    let offense = defense.finalize_defense(rng);
+    let report = offense.odds(&rules);
+    assert_eq!(report.odds.rolls.len(), 1);
+    assert!(report.joins.iter().all(|join| join.side == Side::Attack));
+}
+
+#[test]
+fn attackers_missing_a_color_only_join_on_other_rolls() {
+    let mut game = crate::gamestate::builder::test_game(2, Default::default());
+    let rules = game.rules().clone();
+    let (_, kyle_team) = game.player_by_name("Kyle").unwrap();
+    let defender = game
+        .players()
+        .find(|p| p.team != kyle_team.0)
+        .unwrap()
+        .name
+        .to_string();
+    let teammate = game
+        .players()
+        .find(|p| p.team == kyle_team.0 && p.name != "Kyle")
+        .unwrap()
+        .name
+        .clone();
+    game.player_mut(&teammate).lose_power(PowerType::Blue);
+
+    let state = game.current_state();
+    let defense = DeclaredAttack::declare(state, "Kyle", &defender, PowerType::Red, None).unwrap();
+    let report = defense.odds(&rules);
+    let join = report
+        .joins
+        .iter()
+        .find(|join| join.player == teammate && join.side == Side::Attack)
+        .unwrap();
+    assert!(matches!(join.unable[..], [PowerType::Blue]));
+    // Both list the rolls in the same order.
+    for ((roll, joined), (_, stands)) in join.odds.rolls.iter().zip(&report.odds.rolls) {
+        let added = match roll {
+            PowerType::Blue => 0,
+            &roll => i16::from(state.player_data(&teammate).strength(roll)),
+        };
+        assert_eq!(joined.attack_strength, stands.attack_strength + added);
+    }
+    assert!(report.to_string().contains(&format!(
+        "If {} joins the attack (they can't on a Blue roll)",
+        teammate
+    )));
+}
@@ -0,0 +1,308 @@
+use std::fmt;
+use std::str::FromStr;
+
+use quick_error::quick_error;
+
+use crate::gamestate::builder::StartGameErr;
+use crate::gamestate::players::DeathRule;
+use crate::gamestate::rules::{Rules, TieRule};
+use crate::simulation::{self, SimulationConfig};
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum CalibrationErr {
+        InvalidTarget(target: String) {
+            display("Invalid kill rate target: {} (expected e.g. 1/5-1/3 or 0.2-0.33)", target)
+        }
+    }
+}
+
+// The share of fights that should kill at least one player. The design notes
+// suggest somewhere between 1/5 and 1/3.
+#[derive(Copy, Clone, Debug)]
+pub struct KillRateBand {
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Default for KillRateBand {
+    fn default() -> Self {
+        KillRateBand {
+            min: 1.0 / 5.0,
+            max: 1.0 / 3.0,
+        }
+    }
+}
+
+impl KillRateBand {
+    pub fn contains(&self, rate: f64) -> bool {
+        (self.min..=self.max).contains(&rate)
+    }
+
+    fn distance(&self, rate: f64) -> f64 {
+        (self.min - rate).max(rate - self.max).max(0.0)
+    }
+}
+
+impl FromStr for KillRateBand {
+    type Err = CalibrationErr;
+
+    // "<min>-<max>", where each bound is a decimal or a fraction
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || CalibrationErr::InvalidTarget(s.to_owned());
+        let parse_rate = |rate: &str| -> Option<f64> {
+            match rate.split_once('/') {
+                Some((num, den)) => {
+                    Some(num.trim().parse::<f64>().ok()? / den.trim().parse::<f64>().ok()?)
+                }
+                None => rate.trim().parse().ok(),
+            }
+        };
+        let (min, max) = s.split_once('-').ok_or_else(invalid)?;
+        let band = KillRateBand {
+            min: parse_rate(min).ok_or_else(invalid)?,
+            max: parse_rate(max).ok_or_else(invalid)?,
+        };
+        if !(0.0..=band.max).contains(&band.min) || band.max > 1.0 {
+            return Err(invalid());
+        }
+        Ok(band)
+    }
+}
+
+impl fmt::Display for KillRateBand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:.0}%-{:.0}%", 100.0 * self.min, 100.0 * self.max)
+    }
+}
+
+// The rules to try; every combination is simulated.
+#[derive(Clone, Debug)]
+pub struct CalibrationGrid {
+    pub power_ranges: Vec<(i8, i8)>,
+    pub advantage_multipliers: Vec<i16>,
+    pub death_rules: Vec<DeathRule>,
+    pub ties: Vec<TieRule>,
+}
+
+impl Default for CalibrationGrid {
+    fn default() -> Self {
+        CalibrationGrid {
+            power_ranges: vec![(1, 3), (1, 5), (1, 8)],
+            advantage_multipliers: vec![1, 2, 3],
+            death_rules: vec![
+                DeathRule::TokensDiscarded(1),
+                DeathRule::TokensDiscarded(2),
+                DeathRule::LastTokenDiscarded,
+            ],
+            ties: vec![
+                TieRule::DefenderWins,
+                TieRule::Stalemate,
+                TieRule::BothPrimariesLose,
+                TieRule::Margin(2),
+            ],
+        }
+    }
+}
+
+impl CalibrationGrid {
+    fn settings(&self) -> Vec<Setting> {
+        let mut settings = Vec::new();
+        for &(min_power, max_power) in &self.power_ranges {
+            for &advantage_multiplier in &self.advantage_multipliers {
+                for &death_rule in &self.death_rules {
+                    for &ties in &self.ties {
+                        settings.push(Setting {
+                            min_power,
+                            max_power,
+                            advantage_multiplier,
+                            death_rule,
+                            ties,
+                        });
+                    }
+                }
+            }
+        }
+        settings
+    }
+}
+
+// One point of the grid
+#[derive(Copy, Clone, Debug)]
+pub struct Setting {
+    pub min_power: i8,
+    pub max_power: i8,
+    pub advantage_multiplier: i16,
+    pub death_rule: DeathRule,
+    pub ties: TieRule,
+}
+
+impl Setting {
+    fn apply(&self, rules: &Rules) -> Rules {
+        Rules {
+            min_power: self.min_power,
+            max_power: self.max_power,
+            power_advantage_multiplier: self.advantage_multiplier,
+            death_rule: self.death_rule,
+            ties: self.ties,
+            ..rules.clone()
+        }
+    }
+}
+
+impl fmt::Display for Setting {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "power {}-{}, advantage x{}, death: {:?}, ties: {:?}",
+            self.min_power, self.max_power, self.advantage_multiplier, self.death_rule, self.ties
+        )
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CalibrationConfig {
+    // Every setting is played out with these games; rules the grid doesn't
+    // vary are taken from here.
+    pub simulation: SimulationConfig,
+    pub grid: CalibrationGrid,
+    pub target: KillRateBand,
+}
+
+#[derive(Debug)]
+pub struct SettingResult {
+    pub setting: Setting,
+    pub fights: usize,
+    // Fights in which at least one player died
+    pub kills: usize,
+}
+
+impl SettingResult {
+    pub fn kill_rate(&self) -> f64 {
+        if self.fights == 0 {
+            0.0
+        } else {
+            self.kills as f64 / self.fights as f64
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct CalibrationReport {
+    pub players: usize,
+    pub target: KillRateBand,
+    // Settings the base rules can't be combined with (e.g. a power budget
+    // outside the range) are left out.
+    pub results: Vec<SettingResult>,
+}
+
+impl CalibrationReport {
+    pub fn hits(&self) -> impl Iterator<Item = &SettingResult> {
+        self.results
+            .iter()
+            .filter(move |result| self.target.contains(result.kill_rate()))
+    }
+}
+
+// How many results to show when none hit the target
+const CLOSEST_MISSES: usize = 5;
+
+impl fmt::Display for CalibrationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "Target kill rate: {} of fights, with {} players",
+            self.target, self.players
+        )?;
+        let hits = self.hits().collect::<Vec<_>>();
+        let shown = if hits.is_empty() {
+            writeln!(
+                f,
+                "None of {} settings hit the target; the closest:",
+                self.results.len()
+            )?;
+            let mut closest = self.results.iter().collect::<Vec<_>>();
+            closest.sort_by(|a, b| {
+                let distance = |r: &SettingResult| self.target.distance(r.kill_rate());
+                distance(a).total_cmp(&distance(b))
+            });
+            closest.truncate(CLOSEST_MISSES);
+            closest
+        } else {
+            writeln!(
+                f,
+                "{} of {} settings hit the target:",
+                hits.len(),
+                self.results.len()
+            )?;
+            hits
+        };
+        for result in shown {
+            writeln!(
+                f,
+                "{:>6.1}% of {:>5} fights: {}",
+                100.0 * result.kill_rate(),
+                result.fights,
+                result.setting
+            )?;
+        }
+        Ok(())
+    }
+}
+
+pub fn run(config: &CalibrationConfig) -> Result<CalibrationReport, StartGameErr> {
+    let mut results = Vec::new();
+    for setting in config.grid.settings() {
+        let rules = setting.apply(&config.simulation.rules);
+        if rules.validate().is_err() {
+            continue;
+        }
+        let simulation = SimulationConfig {
+            rules,
+            ..config.simulation.clone()
+        };
+        let (mut fights, mut kills) = (0, 0);
+        for game in 0..simulation.games {
+            let seed = simulation.seed.wrapping_add(game as u64);
+            simulation::play_observed(&simulation, seed, |outcome| {
+                fights += 1;
+                if !outcome.deaths().is_empty() {
+                    kills += 1;
+                }
+            })?;
+        }
+        results.push(SettingResult {
+            setting,
+            fights,
+            kills,
+        });
+    }
+    Ok(CalibrationReport {
+        players: config.simulation.players,
+        target: config.target,
+        results,
+    })
+}
+
+#[test]
+fn harsher_death_rules_kill_more() {
+    let config = CalibrationConfig {
+        simulation: SimulationConfig {
+            games: 3,
+            players: 6,
+            seed: 9,
+            ..Default::default()
+        },
+        grid: CalibrationGrid {
+            power_ranges: vec![(1, 5)],
+            advantage_multipliers: vec![2],
+            death_rules: vec![DeathRule::TokensDiscarded(1), DeathRule::LastTokenDiscarded],
+            ties: vec![TieRule::DefenderWins],
+        },
+        target: "1/5-1/3".parse().unwrap(),
+    };
+    let report = run(&config).unwrap();
+    assert_eq!(report.results.len(), 2);
+    assert!(report.results[0].kill_rate() > report.results[1].kill_rate());
+    assert!("1/3-1/5".parse::<KillRateBand>().is_err());
+}
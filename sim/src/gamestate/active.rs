@@ -1,67 +1,218 @@
-use crate::actions::attack::{Attack, AttackOutcome};
-use crate::gamestate::players::{PName, Player, PlayerAttributePool, PlayersByName};
-use crate::gamestate::teams::{TName, TeamsByName};
-use crate::gamestate::with_history::{GameHistory, HistoryNavigationErr};
+use crate::actions::attack::{Attack, AttackOutcome, SideSwitch};
+use crate::gamestate::assignment;
+use crate::gamestate::builder::StartGameErr;
+use crate::gamestate::log::{EventLog, GameEvent};
+use crate::gamestate::players::{PName, Player, PlayerAttributePool};
+use crate::gamestate::rules::Rules;
+use crate::gamestate::teams::{AllianceErr, TName, TeamsByName};
+use crate::gamestate::victory::{self, GameResult};
+use crate::gamestate::view::{GameMasterView, PlayerView};
+use crate::gamestate::with_history::{GameHistory, HistoryNavigationErr, HistoryStep};
 
 use rand::seq::SliceRandom;
+use rand_pcg::Pcg64;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fmt;
 
-#[derive(Debug)]
-pub struct ActiveGame(GameHistory);
+// All randomness in a game comes from a single generator, so that a game can
+// be reproduced from its seed.
+pub type GameRng = Pcg64;
+
+#[derive(Serialize, Deserialize)]
+pub struct ActiveGame {
+    history: GameHistory,
+    rules: Rules,
+    seed: u64,
+    rng: GameRng,
+    // Games saved before the log existed have an empty one.
+    #[serde(default)]
+    log: EventLog,
+}
 
 impl ActiveGame {
+    // `pinned` maps players to the team they must be on; everyone else is
+    // placed according to the rules (see `TeamAssignment`).
     pub fn new(
         player_names: impl Iterator<Item = String>,
         team_names: impl ExactSizeIterator<Item = String>,
+        pinned: BTreeMap<String, String>,
         mut attribute_pool: impl PlayerAttributePool,
-    ) -> Self {
-        let mut rng = rand::thread_rng();
+        rules: Rules,
+        seed: u64,
+        mut rng: GameRng,
+    ) -> Result<Self, StartGameErr> {
         let mut player_list = player_names.collect::<Vec<_>>();
+        let team_names = team_names.collect::<Vec<_>>();
+        let mut log = EventLog::default();
+        log.record(GameEvent::Setup {
+            team_names: team_names.clone(),
+            player_names: player_list.clone(),
+            pinned: pinned.clone(),
+            rules: rules.clone(),
+            seed,
+        });
         // Randomize player order
         player_list.shuffle(&mut rng);
-        let players_per_team = player_list.len() / team_names.len();
-        let mut extra_players = player_list.len() % team_names.len();
-        let mut teams: TeamsByName = Default::default();
-        for team in team_names {
-            // Add an extra player to the first (players % teams) teams
-            let num_players = if extra_players > 0 {
-                extra_players -= 1;
-                players_per_team + 1
-            } else {
-                players_per_team
-            };
-
-            let players_on_team = player_list.drain(..num_players);
-
-            teams.add(
-                &team,
-                PlayersByName::from(&team, players_on_team, &mut attribute_pool),
-            );
+        let mut teams = assignment::assign(
+            player_list,
+            &team_names,
+            &pinned,
+            rules.team_assignment,
+            &mut attribute_pool,
+            &mut rng,
+        )?;
+        for (player, role) in attribute_pool.roles(&teams, &mut rng)? {
+            teams.player_mut(&player).assign_role(role);
+        }
+        for (protector, ward) in attribute_pool.wards(&teams, &mut rng) {
+            teams.player_mut(&protector).assign_ward(ward);
         }
-        assert!(player_list.is_empty());
         assert!(attribute_pool.is_empty());
-        ActiveGame(GameHistory::starting_with(teams))
+        for player in teams.players() {
+            log.record(GameEvent::Dealt(player.clone()));
+        }
+        Ok(ActiveGame {
+            history: GameHistory::starting_with(teams),
+            rules,
+            seed,
+            rng,
+            log,
+        })
+    }
+
+    // Only for loading saved games (see `save::upgrade`)
+    pub(crate) fn from_parts(history: GameHistory, rules: Rules, seed: u64, rng: GameRng) -> Self {
+        ActiveGame {
+            history,
+            rules,
+            seed,
+            rng,
+            log: Default::default(),
+        }
     }
 
     pub fn current_state(&self) -> &TeamsByName {
-        self.0.current_state()
+        self.history.current_state()
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn rules(&self) -> &Rules {
+        &self.rules
+    }
+
+    // Everything that has happened in this game, including undone attacks
+    pub fn log(&self) -> &EventLog {
+        &self.log
+    }
+
+    // Attacks borrow the current state while they are being built, but still
+    // need the game's RNG to roll the attackers' color.
+    pub fn state_and_rng(&mut self) -> (&TeamsByName, &mut GameRng) {
+        (self.history.current_state(), &mut self.rng)
     }
 
     pub fn preview(&self, attack: Attack) -> AttackOutcome {
-        attack.outcome(self.current_state())
+        attack.outcome(self.current_state(), &self.rules)
     }
 
-    pub fn apply_attack(&mut self, attack: Attack) {
+    // Returns the final result if the attack ended the game.
+    pub fn apply_attack(&mut self, attack: Attack) -> Option<GameResult> {
         let outcome = self.preview(attack);
-        self.apply_attack_outcome(outcome);
+        self.apply_attack_outcome(outcome)
+    }
+
+    pub fn apply_attack_outcome(&mut self, mut attack: AttackOutcome) -> Option<GameResult> {
+        for step in attack.take_steps() {
+            self.log.record(GameEvent::Attack(step));
+        }
+        if let Some(report) = attack.report() {
+            self.log.record(GameEvent::Resolved(report.clone()));
+        }
+        self.history.apply(HistoryStep::Attack(Box::new(attack)));
+        self.result()
+    }
+
+    pub fn is_over(&self) -> bool {
+        victory::is_over(self.current_state())
+    }
+
+    // `None` until the game is over
+    pub fn result(&self) -> Option<GameResult> {
+        if self.is_over() {
+            Some(victory::evaluate(self.current_state()))
+        } else {
+            None
+        }
+    }
+
+    // Alliances are declared publicly, between attacks, and are undone like
+    // attacks. Returns the final result if the only teams left are now all
+    // allied.
+    pub fn form_alliance(&mut self, a: &str, b: &str) -> Result<Option<GameResult>, AllianceErr> {
+        let mut new_state = self.current_state().clone();
+        let (a, b) = new_state.form_alliance(a, b)?;
+        self.log.record(GameEvent::Allied(a.clone(), b.clone()));
+        self.history.apply(HistoryStep::Allied(a, b, new_state));
+        Ok(self.result())
+    }
+
+    pub fn end_alliance(&mut self, a: &str, b: &str) -> Result<(), AllianceErr> {
+        let mut new_state = self.current_state().clone();
+        let (a, b) = new_state.end_alliance(a, b)?;
+        self.log
+            .record(GameEvent::AllianceEnded(a.clone(), b.clone()));
+        self.history
+            .apply(HistoryStep::AllianceEnded(a, b, new_state));
+        Ok(())
+    }
+
+    // Undoes the last attack or alliance change
+    pub fn undo(&mut self) -> Result<(), HistoryNavigationErr> {
+        self.history.undo()?;
+        self.log.record(GameEvent::Undo);
+        Ok(())
     }
 
-    pub fn apply_attack_outcome(&mut self, attack: AttackOutcome) {
-        self.0.apply_attack(attack);
+    pub fn redo(&mut self) -> Result<(), HistoryNavigationErr> {
+        self.history.redo()?;
+        self.log.record(GameEvent::Redo);
+        Ok(())
     }
 
-    pub fn undo_last_attack(&mut self) -> Result<(), HistoryNavigationErr> {
-        self.0.undo_last_attack()
+    // See `GameHistory` for how game states are numbered.
+    pub fn goto(&mut self, node: usize) -> Result<(), HistoryNavigationErr> {
+        self.history.goto(node)?;
+        self.log.record(GameEvent::Goto(node));
+        Ok(())
+    }
+
+    pub fn current_node(&self) -> usize {
+        self.history.current_node()
+    }
+
+    pub fn branches(&self) -> Vec<usize> {
+        self.history.branches()
+    }
+
+    pub fn redo_target(&self) -> Option<usize> {
+        self.history.redo_target()
+    }
+
+    pub fn step_at(&self, node: usize) -> Result<Option<&HistoryStep>, HistoryNavigationErr> {
+        self.history.step_at(node)
+    }
+
+    // Every time an attacker has fought for another team, in order. This is
+    // public knowledge.
+    pub fn side_switches(&self) -> impl Iterator<Item = &SideSwitch> {
+        self.history
+            .attacks_so_far()
+            .into_iter()
+            .filter_map(AttackOutcome::side_switch)
     }
 
     pub fn player_by_name(&self, name: &str) -> Option<(PName, TName)> {
@@ -73,7 +224,7 @@ impl ActiveGame {
     }
 
     pub fn player_mut(&mut self, player: &PName) -> &mut Player {
-        self.0.current_mut().player_mut(player)
+        self.history.current_mut().player_mut(player)
     }
 
     pub fn players(&self) -> impl Iterator<Item = &Player> {
@@ -81,10 +232,19 @@ impl ActiveGame {
     }
 
     pub fn players_mut(&mut self) -> impl Iterator<Item = &mut Player> {
-        self.0.current_mut().players_mut()
+        self.history.current_mut().players_mut()
+    }
+
+    // Panics if the player does not exist
+    pub fn view_for(&self, player: &PName) -> PlayerView<'_> {
+        PlayerView::new(self.current_state(), &self.rules, player)
     }
 
-    pub fn pretty_player(&self, name: & PName) -> String {
+    pub fn game_master_view(&self) -> GameMasterView<'_> {
+        GameMasterView::new(self.current_state())
+    }
+
+    pub fn pretty_player(&self, name: &PName) -> String {
         self.current_state().pretty_player(name)
     }
 
@@ -93,9 +253,129 @@ impl ActiveGame {
     }
 }
 
+// The seed and RNG are left out: with them, anyone could work out every role
+// and ward, and predict the attack color rolls. So is the log, which records
+// the seed.
+impl fmt::Debug for ActiveGame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ActiveGame")
+            .field("history", &self.history)
+            .field("rules", &self.rules)
+            .finish_non_exhaustive()
+    }
+}
+
 impl fmt::Display for ActiveGame {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         writeln!(f, "Game state:")?;
         writeln!(f, "{}", self.current_state())
     }
 }
+
+#[test]
+fn redo_and_branches() {
+    use crate::actions::attack::DeclaredAttack;
+    use crate::gamestate::builder::test_game;
+    use crate::gamestate::power::PowerType;
+
+    let mut game = test_game(5, Default::default());
+    let attack = |game: &mut ActiveGame, power_type| {
+        let attacker = game
+            .players()
+            .find(|p| p.team == "Danes")
+            .unwrap()
+            .name
+            .to_string();
+        let (state, rng) = game.state_and_rng();
+        let attack = DeclaredAttack::declare(state, &attacker, "Kyle", power_type, None)
+            .unwrap()
+            .finalize_defense(rng)
+            .finalize_offense();
+        game.apply_attack(attack);
+    };
+
+    attack(&mut game, PowerType::Green);
+    attack(&mut game, PowerType::Green);
+    assert_eq!(game.current_node(), 2);
+    game.undo().unwrap();
+    game.undo().unwrap();
+    assert!(game.redo().is_ok());
+    assert_eq!(game.current_node(), 1);
+
+    // A new attack from the start of the game is a second branch; redo now
+    // follows it.
+    game.goto(0).unwrap();
+    attack(&mut game, PowerType::Red);
+    assert_eq!(game.current_node(), 3);
+    game.undo().unwrap();
+    assert_eq!(game.branches(), vec![1, 3]);
+    assert_eq!(game.redo_target(), Some(3));
+
+    // Going back to the old branch makes it the redo path again.
+    game.goto(2).unwrap();
+    game.goto(0).unwrap();
+    assert_eq!(game.redo_target(), Some(1));
+    game.redo().unwrap();
+    game.redo().unwrap();
+    assert_eq!(game.current_node(), 2);
+    assert!(matches!(
+        game.redo(),
+        Err(HistoryNavigationErr::NothingToRedo)
+    ));
+    assert!(matches!(
+        game.goto(4),
+        Err(HistoryNavigationErr::NoSuchNode(4))
+    ));
+}
+
+#[test]
+fn alliances_are_history_steps() {
+    use crate::actions::attack::DeclaredAttack;
+    use crate::gamestate::builder::test_game;
+    use crate::gamestate::power::PowerType;
+
+    let mut game = test_game(5, Default::default());
+    let attacker = game
+        .players()
+        .find(|p| p.team == "Danes")
+        .unwrap()
+        .name
+        .to_string();
+    let (state, rng) = game.state_and_rng();
+    let attack = DeclaredAttack::declare(state, &attacker, "Kyle", PowerType::Green, None)
+        .unwrap()
+        .finalize_defense(rng)
+        .finalize_offense();
+    game.apply_attack(attack);
+    let allied = |game: &ActiveGame| game.current_state().are_friendly("Geats", "Danes");
+
+    // With only two teams, the alliance ends the game.
+    assert!(game.form_alliance("Geats", "Danes").unwrap().is_some());
+    assert_eq!(game.current_node(), 2);
+    game.undo().unwrap();
+    assert!(!allied(&game));
+    assert!(!game.is_over());
+    game.undo().unwrap();
+    game.redo().unwrap();
+    assert_eq!(game.current_node(), 1);
+    assert!(!allied(&game));
+    game.redo().unwrap();
+    assert!(allied(&game));
+    assert!(game.is_over());
+
+    // A different line of play from the attack doesn't see the alliance.
+    game.goto(1).unwrap();
+    game.end_alliance("Geats", "Danes").unwrap_err();
+    game.form_alliance("Danes", "Geats").unwrap();
+    game.end_alliance("Geats", "Danes").unwrap();
+    assert_eq!(game.current_node(), 4);
+    assert!(!allied(&game));
+    game.goto(1).unwrap();
+    assert_eq!(game.branches(), vec![2, 3]);
+    assert_eq!(
+        game.step_at(3).unwrap().unwrap().to_string(),
+        "Teams Danes and Geats ally"
+    );
+    game.goto(2).unwrap();
+    assert!(allied(&game));
+}
@@ -0,0 +1,218 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+use crate::gamestate::active::GameRng;
+use crate::gamestate::builder::StartGameErr;
+use crate::gamestate::players::{Player, PlayerAttributePool, PlayersByName};
+use crate::gamestate::rules::TeamAssignment;
+use crate::gamestate::teams::TeamsByName;
+
+// Places every player on a team and deals them their power. `players` should
+// already be shuffled; `pinned` maps players to the team they must be on.
+pub(crate) fn assign(
+    players: Vec<String>,
+    team_names: &[String],
+    pinned: &BTreeMap<String, String>,
+    mode: TeamAssignment,
+    attribute_pool: &mut dyn PlayerAttributePool,
+    rng: &mut GameRng,
+) -> Result<TeamsByName, StartGameErr> {
+    let sizes = team_sizes(team_names, players.len(), pinned)?;
+    let mut teams = TeamsByName::default();
+    match mode {
+        TeamAssignment::Random => {
+            let mut unpinned = players
+                .into_iter()
+                .filter(|name| !pinned.contains_key(name))
+                .collect::<Vec<_>>();
+            for (team, size) in team_names.iter().zip(sizes) {
+                let pins = pinned_to(pinned, team).collect::<Vec<_>>();
+                let open = size - pins.len();
+                let members = pins.into_iter().chain(unpinned.drain(..open));
+                teams.add(
+                    team,
+                    PlayersByName::from(team, members, attribute_pool, rng),
+                );
+            }
+        }
+        TeamAssignment::Balanced => {
+            let players = players
+                .into_iter()
+                .map(|name| Player::without_team(name, attribute_pool, rng))
+                .collect();
+            let members = balance(players, team_names, &sizes, pinned);
+            for (team, members) in team_names.iter().zip(members) {
+                teams.add(team, members.into_iter().collect());
+            }
+        }
+    }
+    Ok(teams)
+}
+
+fn pinned_to<'a>(
+    pinned: &'a BTreeMap<String, String>,
+    team: &'a str,
+) -> impl Iterator<Item = String> + 'a {
+    pinned
+        .iter()
+        .filter(move |(_, pinned_team)| *pinned_team == team)
+        .map(|(player, _)| player.clone())
+}
+
+// Team sizes differ by at most one. The extra players go to the teams with
+// the most pinned players, then to the teams listed first.
+fn team_sizes(
+    team_names: &[String],
+    num_players: usize,
+    pinned: &BTreeMap<String, String>,
+) -> Result<Vec<usize>, StartGameErr> {
+    let pins = team_names
+        .iter()
+        .map(|team| pinned_to(pinned, team).count())
+        .collect::<Vec<_>>();
+    let mut sizes = vec![num_players / team_names.len(); team_names.len()];
+    let mut by_pins = (0..team_names.len()).collect::<Vec<_>>();
+    by_pins.sort_by_key(|&index| Reverse(pins[index]));
+    for &index in by_pins.iter().take(num_players % team_names.len()) {
+        sizes[index] += 1;
+    }
+    for ((team, &size), &pins) in team_names.iter().zip(&sizes).zip(&pins) {
+        if pins > size {
+            return Err(StartGameErr::TooManyPinned(team.clone(), size));
+        }
+    }
+    Ok(sizes)
+}
+
+// Pinned players are placed first. The rest go, strongest first, to
+// whichever team with room is weakest so far; then players are swapped
+// between teams for as long as that brings the totals closer together.
+fn balance(
+    players: Vec<Player>,
+    team_names: &[String],
+    sizes: &[usize],
+    pinned: &BTreeMap<String, String>,
+) -> Vec<Vec<Player>> {
+    let mut members = vec![Vec::new(); team_names.len()];
+    let mut free = Vec::new();
+    for mut player in players {
+        match pinned.get(player.name.as_str()) {
+            Some(team) => {
+                let index = team_names
+                    .iter()
+                    .position(|name| name == team)
+                    .expect("Players are only pinned to known teams");
+                player.team = team.clone();
+                members[index].push(player);
+            }
+            None => free.push(player),
+        }
+    }
+    let num_pinned = members.iter().map(Vec::len).collect::<Vec<_>>();
+    let total = |team: &Vec<Player>| team.iter().map(Player::total_strength).sum::<i16>();
+
+    // The sort is stable, so equally strong players stay shuffled.
+    free.sort_by_key(|player| Reverse(player.total_strength()));
+    for player in free {
+        let index = (0..members.len())
+            .filter(|&index| members[index].len() < sizes[index])
+            .min_by_key(|&index| total(&members[index]))
+            .expect("Team sizes add up to the number of players");
+        members[index].push(player);
+    }
+
+    // Each swap strictly lowers the sum of squared totals, so this ends.
+    let squares = |a: i16, b: i16| i32::from(a).pow(2) + i32::from(b).pow(2);
+    let mut swapped = true;
+    while swapped {
+        swapped = false;
+        for a in 0..members.len() {
+            for b in a + 1..members.len() {
+                let (total_a, total_b) = (total(&members[a]), total(&members[b]));
+                let best = (num_pinned[a]..members[a].len())
+                    .flat_map(|i| (num_pinned[b]..members[b].len()).map(move |j| (i, j)))
+                    .map(|(i, j)| {
+                        let moved = members[a][i].total_strength() - members[b][j].total_strength();
+                        (squares(total_a - moved, total_b + moved), i, j)
+                    })
+                    .min();
+                if let Some((after, i, j)) = best {
+                    if after < squares(total_a, total_b) {
+                        let (left, right) = members.split_at_mut(b);
+                        std::mem::swap(&mut left[a][i], &mut right[0][j]);
+                        swapped = true;
+                    }
+                }
+            }
+        }
+    }
+
+    for (team, members) in team_names.iter().zip(&mut members) {
+        for player in members {
+            player.team = team.clone();
+        }
+    }
+    members
+}
+
+#[test]
+fn pinned_players_stay_put_and_balanced_teams_are_even() {
+    use crate::gamestate::builder::Setup;
+    use crate::gamestate::rules::Rules;
+
+    let setup = |seed, team_assignment| {
+        Setup::new_game()
+            .with_seed(seed)
+            .with_rules(Rules {
+                team_assignment,
+                ..Default::default()
+            })
+            .add_team_or_panic("Geats")
+            .add_team_or_panic("Danes")
+            .add_player_to_team_or_panic("Kyle", "Geats")
+            .add_player_to_team_or_panic("Laura", "Danes")
+            .add_player_or_panic("Brandon")
+            .add_player_or_panic("Suzie")
+            .add_player_or_panic("Lauren")
+            .add_player_or_panic("Annabelle")
+            .add_player_or_panic("Nate")
+            .add_player_or_panic("Erin")
+    };
+    let spread = |seed, mode| {
+        let game = setup(seed, mode).finalize().unwrap();
+        let players = game.players().collect::<Vec<_>>();
+        let team_total = |team: &str| {
+            players
+                .iter()
+                .filter(|p| p.team == team)
+                .map(|p| p.total_strength())
+                .sum::<i16>()
+        };
+        let kyle = players.iter().find(|p| p.name == "Kyle").unwrap();
+        let laura = players.iter().find(|p| p.name == "Laura").unwrap();
+        assert_eq!(
+            (kyle.team.as_str(), laura.team.as_str()),
+            ("Geats", "Danes")
+        );
+        (team_total("Geats") - team_total("Danes")).abs()
+    };
+    for seed in 0..10 {
+        let balanced = spread(seed, TeamAssignment::Balanced);
+        assert!(balanced <= 2);
+        assert!(balanced <= spread(seed, TeamAssignment::Random));
+    }
+
+    let crowded = Setup::new_game()
+        .add_team_or_panic("Geats")
+        .add_team_or_panic("Danes")
+        .add_player_to_team_or_panic("Kyle", "Geats")
+        .add_player_to_team_or_panic("Laura", "Geats")
+        .add_player_to_team_or_panic("Brandon", "Geats")
+        .add_player_to_team_or_panic("Suzie", "Geats")
+        .add_player_or_panic("Lauren")
+        .add_player_or_panic("Annabelle");
+    assert!(matches!(
+        crowded.finalize(),
+        Err(StartGameErr::TooManyPinned(..))
+    ));
+}
@@ -1,81 +1,264 @@
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fmt;
 
 use itertools::Itertools;
 use quick_error::quick_error;
-use rand::distributions::Uniform;
-use rand::seq::IteratorRandom;
-use rand::Rng;
+use rand::seq::{IteratorRandom, SliceRandom};
+use rand::{Rng, SeedableRng};
 
-use crate::gamestate::active::ActiveGame;
-use crate::gamestate::players::{PlayerAttributePool, Role};
-use crate::gamestate::power::Power;
+use crate::gamestate::active::{ActiveGame, GameRng};
+use crate::gamestate::players::{PName, PlayerAttributePool, Role};
+use crate::gamestate::power::{Power, PowerDistribution};
+use crate::gamestate::rules::{RoleCounts, RolePlacement, Rules, RulesErr};
+use crate::gamestate::teams::TeamsByName;
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct Setup {
     team_names: BTreeSet<String>,
     player_names: BTreeSet<String>,
+    // Players who must be on a particular team
+    pinned: BTreeMap<String, String>,
+    // Lowercase; see `reserve_names`
+    reserved_names: BTreeSet<String>,
+    rules: Rules,
+    seed: Option<u64>,
 }
 
 struct PlayerAttributeProvider {
     power_token_sets: Vec<Power>,
-    num_players_remaining: usize,
-    roles: Vec<Role>,
-    destined: BTreeSet<String>,
+    // Only for `PowerDistribution::BalancedTeams`: each team is dealt from its
+    // own shuffled copy of `power_token_sets`.
+    team_token_sets: Option<BTreeMap<String, Vec<Power>>>,
+    num_powers_remaining: usize,
+    // Taken once roles have been dealt
+    role_counts: Option<RoleCounts>,
+    role_placement: RolePlacement,
+    cross_team_ward_ratio: f64,
 }
 
 impl PlayerAttributeProvider {
-    fn new(player_names: &BTreeSet<String>) -> Self {
-        let num_players = player_names.len();
-        let mut rng = rand::thread_rng();
-        let power_range: Uniform<i8> = Uniform::new(1, 6);
-        let mut pool = PlayerAttributeProvider {
-            power_token_sets: Vec::with_capacity(num_players),
-            num_players_remaining: num_players,
-            roles: Default::default(),
-            destined: Default::default(),
+    fn new(num_players: usize, num_teams: usize, rules: &Rules, rng: &mut GameRng) -> Self {
+        let balanced = matches!(rules.power_distribution, PowerDistribution::BalancedTeams);
+        // Enough for the largest team
+        let num_sets = if balanced {
+            num_players.div_ceil(num_teams)
+        } else {
+            num_players
         };
-        pool.power_token_sets.resize_with(
-            num_players,
-            // TODO: These should not be randomized independently
-            || Power::randomize(power_range, &mut rng),
-        );
-        // Q: How many 'destined'?
-        let destined = player_names
-            .iter()
-            .choose(&mut rng)
-            .expect("No players in game")
-            .clone();
-        pool.destined.insert(destined.clone());
-        pool.roles.push(Role::Prophet { target: destined });
-        pool.roles.push(Role::Traitor);
-        pool
+        PlayerAttributeProvider {
+            power_token_sets: rules.power_distribution.deal(
+                num_sets,
+                rules.min_power,
+                rules.max_power,
+                rng,
+            ),
+            team_token_sets: balanced.then(BTreeMap::new),
+            num_powers_remaining: num_players,
+            role_counts: Some(rules.roles.for_players(num_players)),
+            role_placement: rules.role_placement,
+            cross_team_ward_ratio: rules.cross_team_ward_ratio,
+        }
     }
 }
 
 impl PlayerAttributePool for PlayerAttributeProvider {
-    fn next_power(&mut self) -> Power {
-        self.power_token_sets
-            .pop()
-            .expect("No more power tokens left")
-    }
-    fn next_role(&mut self, name: &str) -> Option<Role> {
-        self.num_players_remaining -= 1;
-        if self.destined.contains(name) {
-            return Some(Role::Destined);
-        }
-        let probability_has_role =
-            self.roles.len() as f64 / (self.num_players_remaining as f64 + 1.0);
-        let mut rng = rand::thread_rng();
-        if rng.gen_bool(probability_has_role) {
-            let index = rng.sample(Uniform::new(0, self.roles.len()));
-            Some(self.roles.remove(index))
-        } else {
-            None
+    fn next_power(&mut self, team: Option<&str>, rng: &mut GameRng) -> Power {
+        self.num_powers_remaining -= 1;
+        let sets = match (&mut self.team_token_sets, team) {
+            (Some(teams), Some(team)) => teams.entry(team.to_owned()).or_insert_with(|| {
+                let mut sets = self.power_token_sets.clone();
+                sets.shuffle(rng);
+                sets
+            }),
+            // `Rules::validate` keeps balanced teams from being dealt without
+            // knowing the team.
+            _ => &mut self.power_token_sets,
+        };
+        sets.pop().expect("No more power tokens left")
+    }
+    // Players are shuffled, and roles are dealt in that order, backtracking
+    // whenever the placement rules leave a role with nobody to take it.
+    // Destined players are chosen first, since the prophets' placement
+    // depends on them.
+    fn roles(
+        &mut self,
+        teams: &TeamsByName,
+        rng: &mut GameRng,
+    ) -> Result<HashMap<PName, Role>, StartGameErr> {
+        let counts = self.role_counts.take().expect("Roles were already dealt");
+        let team_sizes = teams
+            .teams()
+            .map(|(name, team)| (name.0.clone(), team.players().count()))
+            .collect::<HashMap<_, _>>();
+        // `teams.players()` is in a consistent order, so this is reproducible.
+        let mut players = teams
+            .players()
+            .map(|p| (p.name.clone(), p.team.clone()))
+            .collect::<Vec<_>>();
+        players.shuffle(rng);
+        let mut search = RoleSearch {
+            roles: vec![None; players.len()],
+            players: &players,
+            counts,
+            placement: self.role_placement,
+            team_sizes: &team_sizes,
+            first_failure: None,
+        };
+        if !search.place_destined(0, &mut Vec::new()) {
+            return Err(search
+                .first_failure
+                .expect("A failed search records why it failed"));
         }
+        Ok(players
+            .iter()
+            .zip(search.roles)
+            .filter_map(|((name, _), role)| Some((name.clone(), role?)))
+            .collect())
+    }
+    // Every player protects exactly one other player, and is protected by
+    // exactly one other player.
+    fn wards(&mut self, teams: &TeamsByName, rng: &mut GameRng) -> HashMap<PName, PName> {
+        // `teams.players()` is in a consistent order, so this is reproducible.
+        let mut protectors = teams
+            .players()
+            .map(|p| (p.name.clone(), p.team.clone()))
+            .collect::<Vec<_>>();
+        protectors.shuffle(rng);
+        let mut unprotected = protectors.clone();
+        let mut wards: HashMap<PName, PName> = HashMap::new();
+        for (protector, team) in &protectors {
+            let want_cross_team = rng.gen_bool(self.cross_team_ward_ratio);
+            let candidates = (0..unprotected.len())
+                .filter(|&i| unprotected[i].0 != *protector)
+                .collect::<Vec<_>>();
+            let preferred = candidates
+                .iter()
+                .copied()
+                .filter(|&i| (unprotected[i].1 != *team) == want_cross_team)
+                .collect::<Vec<_>>();
+            let chosen = if preferred.is_empty() {
+                candidates.choose(rng)
+            } else {
+                preferred.choose(rng)
+            };
+            match chosen {
+                Some(&index) => {
+                    let (ward, _) = unprotected.swap_remove(index);
+                    wards.insert(protector.clone(), ward);
+                }
+                None => {
+                    // Only the protector themselves is left unprotected, so
+                    // trade wards with someone who has already been assigned.
+                    let (other, _) = unprotected.pop().expect("No wards left to assign");
+                    let swap_with = wards
+                        .keys()
+                        .choose(rng)
+                        .expect("A lone player cannot protect anyone")
+                        .clone();
+                    let ward = wards.insert(swap_with, other).unwrap();
+                    wards.insert(protector.clone(), ward);
+                }
+            }
+        }
+        wards
     }
     fn is_empty(&self) -> bool {
-        self.power_token_sets.is_empty() && self.num_players_remaining == 0 && self.roles.is_empty()
+        self.num_powers_remaining == 0 && self.role_counts.is_none()
+    }
+}
+
+// A depth-first search for a deal of roles. Players on the same team are
+// interchangeable as far as the placement rules go, so at each step only the
+// first untried player from each team is tried.
+struct RoleSearch<'a> {
+    // Shuffled
+    players: &'a [(PName, String)],
+    roles: Vec<Option<Role>>,
+    counts: RoleCounts,
+    placement: RolePlacement,
+    team_sizes: &'a HashMap<String, usize>,
+    // The first role that couldn't be placed, reported if no deal works
+    first_failure: Option<StartGameErr>,
+}
+
+impl RoleSearch<'_> {
+    fn place_destined(&mut self, start: usize, destined: &mut Vec<usize>) -> bool {
+        if destined.len() == self.counts.destined {
+            return self.place_prophets(0, destined);
+        }
+        let players = self.players;
+        let mut tried = HashSet::new();
+        for (index, (_, team)) in players.iter().enumerate().skip(start) {
+            if !tried.insert(team) {
+                continue;
+            }
+            self.roles[index] = Some(Role::Destined);
+            destined.push(index);
+            if self.place_destined(index + 1, destined) {
+                return true;
+            }
+            destined.pop();
+            self.roles[index] = None;
+        }
+        false
+    }
+
+    fn place_prophets(&mut self, prophet: usize, destined: &[usize]) -> bool {
+        if prophet == self.counts.prophets {
+            return self.place_traitors();
+        }
+        let players = self.players;
+        // Prophecies are spread evenly over the destined players.
+        let group = destined
+            .iter()
+            .cycle()
+            .skip(prophet * self.counts.targets_per_prophet)
+            .take(self.counts.targets_per_prophet)
+            .map(|&index| &players[index])
+            .collect::<Vec<_>>();
+        let mut tried = HashSet::new();
+        for (index, (_, team)) in players.iter().enumerate() {
+            if self.roles[index].is_some()
+                || !group
+                    .iter()
+                    .all(|(_, target_team)| self.placement.prophets.allows(team, target_team))
+                || !tried.insert(team)
+            {
+                continue;
+            }
+            self.roles[index] = Some(Role::Prophet {
+                targets: group.iter().map(|(name, _)| name.to_string()).collect(),
+            });
+            if self.place_prophets(prophet + 1, destined) {
+                return true;
+            }
+            self.roles[index] = None;
+        }
+        self.first_failure.get_or_insert_with(|| {
+            StartGameErr::CannotPlaceProphet(group.iter().map(|(name, _)| name).join(", "))
+        });
+        false
+    }
+
+    // Traitors only need a large enough team, so any eligible players will do.
+    fn place_traitors(&mut self) -> bool {
+        let min_team_size = self.placement.traitor_min_team_size;
+        let traitors = (0..self.players.len())
+            .filter(|&index| {
+                self.roles[index].is_none()
+                    && self.team_sizes[&self.players[index].1] >= min_team_size
+            })
+            .take(self.counts.traitors)
+            .collect::<Vec<_>>();
+        if traitors.len() < self.counts.traitors {
+            self.first_failure
+                .get_or_insert(StartGameErr::CannotPlaceTraitor(min_team_size));
+            return false;
+        }
+        for index in traitors {
+            self.roles[index] = Some(Role::Traitor);
+        }
+        true
     }
 }
 
@@ -87,6 +270,15 @@ quick_error! {
     pub enum GameSetupErr {
         PlayerNameDuplicated {}
         TeamNameDuplicated {}
+        TeamNotFound {}
+        EmptyName {}
+        NameCollides(name: String, existing: String) {
+            display("{} is too similar to the existing name {}", name, existing)
+        }
+        ReservedName(name: String) {
+            display("{} is a shell keyword and cannot be used as a name", name)
+        }
+        PlayerNotFound {}
     }
 }
 
@@ -95,6 +287,18 @@ quick_error! {
     pub enum StartGameErr {
         TeamsNotEstablished {}
         TooFewPlayers {}
+        TooManyRoles(roles: usize, players: usize) {
+            display("{} special roles cannot be dealt to {} players", roles, players)
+        }
+        CannotPlaceProphet(targets: String) {
+            display("No player can be the prophet of {} under the placement rules", targets)
+        }
+        CannotPlaceTraitor(min_team_size: usize) {
+            display("No player is on a team of at least {} who could be the traitor", min_team_size)
+        }
+        TooManyPinned(team: String, size: usize) {
+            display("More players are pinned to team {} than the {} it can hold", team, size)
+        }
     }
 }
 
@@ -109,18 +313,28 @@ impl Setup {
         if self.team_names.len() < 2 {
             return Err(StartGameErr::TeamsNotEstablished);
         }
-        // TODO DESIGN min number of players?
         let num_players = self.player_names.len();
-        if num_players < self.team_names.len() * 3 {
+        if num_players < self.team_names.len() * self.rules.min_players_per_team {
             return Err(StartGameErr::TooFewPlayers);
         }
+        let roles = self.rules.roles.for_players(num_players).total();
+        if roles > num_players {
+            return Err(StartGameErr::TooManyRoles(roles, num_players));
+        }
 
-        let attributes_provider = PlayerAttributeProvider::new(&self.player_names);
-        Ok(ActiveGame::new(
+        let seed = self.seed.unwrap_or_else(|| rand::thread_rng().gen());
+        let mut rng = GameRng::seed_from_u64(seed);
+        let attributes_provider =
+            PlayerAttributeProvider::new(num_players, self.team_names.len(), &self.rules, &mut rng);
+        ActiveGame::new(
             self.player_names.into_iter(),
             self.team_names.into_iter(),
+            self.pinned,
             attributes_provider,
-        ))
+            self.rules,
+            seed,
+            rng,
+        )
     }
 
     pub fn add_team_or_panic(mut self, name: &str) -> Self {
@@ -135,21 +349,154 @@ impl Setup {
         self
     }
 
+    pub fn add_player_to_team_or_panic(mut self, name: &str, team: &str) -> Self {
+        self.add_player_to_team(name, team).unwrap();
+        println!("Added player: {} (team {})", name, team);
+        self
+    }
+
+    // Without a seed, a random one is chosen when the game starts.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.set_seed(seed);
+        self
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = Some(seed);
+    }
+
+    pub fn with_rules(mut self, rules: Rules) -> Self {
+        self.set_rules(rules).unwrap();
+        self
+    }
+
+    // Every rule is set at once, so that they are always validated together.
+    pub fn set_rules(&mut self, rules: Rules) -> Result<(), RulesErr> {
+        rules.validate()?;
+        self.rules = rules;
+        Ok(())
+    }
+
+    pub fn rules(&self) -> &Rules {
+        &self.rules
+    }
+
     pub fn add_team(&mut self, name: &str) -> OptErr<GameSetupErr> {
-        let already_exists = !self.team_names.insert(String::from(name));
-        if already_exists {
-            Err(GameSetupErr::TeamNameDuplicated)
-        } else {
-            Ok(())
+        if self.team_names.contains(name) {
+            return Err(GameSetupErr::TeamNameDuplicated);
         }
+        self.validate_name(name, &self.team_names, None)?;
+        self.team_names.insert(String::from(name));
+        Ok(())
     }
 
     pub fn add_player(&mut self, name: &str) -> OptErr<GameSetupErr> {
-        let already_exists = !self.player_names.insert(String::from(name));
-        if already_exists {
-            Err(GameSetupErr::PlayerNameDuplicated)
-        } else {
-            Ok(())
+        if self.player_names.contains(name) {
+            return Err(GameSetupErr::PlayerNameDuplicated);
+        }
+        self.validate_name(name, &self.player_names, None)?;
+        self.player_names.insert(String::from(name));
+        Ok(())
+    }
+
+    // The team must already have been added.
+    pub fn add_player_to_team(&mut self, name: &str, team: &str) -> OptErr<GameSetupErr> {
+        if !self.team_names.contains(team) {
+            return Err(GameSetupErr::TeamNotFound);
+        }
+        self.add_player(name)?;
+        self.pinned.insert(name.to_owned(), team.to_owned());
+        Ok(())
+    }
+
+    // Players pinned to a removed team are kept, but may now be put on any
+    // team.
+    pub fn remove_team(&mut self, name: &str) -> OptErr<GameSetupErr> {
+        if !self.team_names.remove(name) {
+            return Err(GameSetupErr::TeamNotFound);
+        }
+        self.pinned.retain(|_, team| team != name);
+        Ok(())
+    }
+
+    pub fn remove_player(&mut self, name: &str) -> OptErr<GameSetupErr> {
+        if !self.player_names.remove(name) {
+            return Err(GameSetupErr::PlayerNotFound);
+        }
+        self.pinned.remove(name);
+        Ok(())
+    }
+
+    // The new name may differ from the old one only in case.
+    pub fn rename_team(&mut self, old: &str, new: &str) -> OptErr<GameSetupErr> {
+        if !self.team_names.contains(old) {
+            return Err(GameSetupErr::TeamNotFound);
+        }
+        self.validate_name(new, &self.team_names, Some(old))?;
+        self.team_names.remove(old);
+        self.team_names.insert(new.to_owned());
+        for team in self.pinned.values_mut().filter(|team| *team == old) {
+            *team = new.to_owned();
+        }
+        Ok(())
+    }
+
+    pub fn rename_player(&mut self, old: &str, new: &str) -> OptErr<GameSetupErr> {
+        if !self.player_names.contains(old) {
+            return Err(GameSetupErr::PlayerNotFound);
+        }
+        self.validate_name(new, &self.player_names, Some(old))?;
+        self.player_names.remove(old);
+        self.player_names.insert(new.to_owned());
+        if let Some(team) = self.pinned.remove(old) {
+            self.pinned.insert(new.to_owned(), team);
+        }
+        Ok(())
+    }
+
+    pub fn teams(&self) -> impl Iterator<Item = &str> {
+        self.team_names.iter().map(String::as_str)
+    }
+
+    // Each player, with the team they are pinned to (if any)
+    pub fn players(&self) -> impl Iterator<Item = (&str, Option<&str>)> {
+        self.player_names
+            .iter()
+            .map(move |name| (name.as_str(), self.pinned.get(name).map(String::as_str)))
+    }
+
+    // Names that can't be used for teams or players, in any case (e.g. the
+    // commands of a shell that takes names as arguments)
+    pub fn reserve_names<'a>(&mut self, names: impl IntoIterator<Item = &'a str>) {
+        self.reserved_names
+            .extend(names.into_iter().map(str::to_lowercase));
+    }
+
+    // Team names must differ from each other by more than case, as must
+    // player names; a player may share a name with a team.
+    fn validate_name(
+        &self,
+        name: &str,
+        existing: &BTreeSet<String>,
+        replacing: Option<&str>,
+    ) -> OptErr<GameSetupErr> {
+        if name.trim().is_empty() {
+            return Err(GameSetupErr::EmptyName);
+        }
+        let lowercase = name.to_lowercase();
+        if self.reserved_names.contains(&lowercase) {
+            return Err(GameSetupErr::ReservedName(name.to_owned()));
+        }
+        match existing
+            .iter()
+            .filter(|existing| Some(existing.as_str()) != replacing)
+            .find(|existing| existing.to_lowercase() == lowercase)
+        {
+            Some(existing) => Err(GameSetupErr::NameCollides(
+                name.to_owned(),
+                existing.clone(),
+            )),
+            None => Ok(()),
         }
     }
 }
@@ -160,7 +507,238 @@ impl fmt::Display for Setup {
             f,
             "Teams: {}, Players: {}",
             self.team_names.iter().join(", "),
-            self.player_names.iter().join(", ")
+            self.player_names
+                .iter()
+                .map(|name| match self.pinned.get(name) {
+                    Some(team) => format!("{} ({})", name, team),
+                    None => name.clone(),
+                })
+                .join(", ")
         )
     }
 }
+
+// Two teams of three, the game most tests start from
+#[cfg(test)]
+pub(crate) fn test_setup(seed: u64, rules: Rules) -> Setup {
+    Setup::new_game()
+        .with_seed(seed)
+        .with_rules(rules)
+        .add_team_or_panic("Geats")
+        .add_player_or_panic("Kyle")
+        .add_player_or_panic("Laura")
+        .add_player_or_panic("Brandon")
+        .add_team_or_panic("Danes")
+        .add_player_or_panic("Suzie")
+        .add_player_or_panic("Lauren")
+        .add_player_or_panic("Annabelle")
+}
+
+#[cfg(test)]
+pub(crate) fn test_game(seed: u64, rules: Rules) -> ActiveGame {
+    test_setup(seed, rules).finalize().unwrap()
+}
+
+#[test]
+fn every_player_has_one_protector() {
+    use crate::gamestate::players::Player;
+
+    for ratio in [0.0, 0.5, 1.0] {
+        let rules = Rules {
+            cross_team_ward_ratio: ratio,
+            ..Default::default()
+        };
+        let game = test_setup(0, rules)
+            .add_player_or_panic("Luna")
+            .finalize()
+            .unwrap();
+        let wards = game
+            .players()
+            .map(|p| p.ward().expect("Every player should have a ward"))
+            .collect::<BTreeSet<_>>();
+        assert_eq!(wards.len(), 7);
+        assert!(game.players().all(|p: &Player| p.ward() != Some(&p.name)));
+    }
+    assert!(matches!(
+        Setup::new_game().set_rules(Rules {
+            cross_team_ward_ratio: 1.5,
+            ..Default::default()
+        }),
+        Err(RulesErr::InvalidRatio(_))
+    ));
+}
+
+#[test]
+fn same_seed_same_game() {
+    let (first, second) = (
+        test_game(1, Default::default()),
+        test_game(1, Default::default()),
+    );
+    assert_eq!(first.seed(), 1);
+    assert_eq!(
+        format!("{:?}", first.current_state()),
+        format!("{:?}", second.current_state())
+    );
+    // `Debug` hides secrets, so compare those separately.
+    assert_eq!(
+        first.game_master_view().to_string(),
+        second.game_master_view().to_string()
+    );
+}
+
+#[test]
+fn power_distributions_keep_their_promises() {
+    use crate::gamestate::players::Player;
+    use crate::gamestate::power::PowerType;
+
+    let new_game = |power_distribution| {
+        test_game(
+            4,
+            Rules {
+                power_distribution,
+                ..Default::default()
+            },
+        )
+    };
+    let total = |p: &Player| -> i16 {
+        PowerType::ALL
+            .iter()
+            .map(|&ptype| i16::from(p.strength(ptype)))
+            .sum()
+    };
+
+    let budget = new_game(PowerDistribution::Budget(9));
+    assert!(budget.players().all(|p| total(p) == 9));
+
+    let bag = new_game(PowerDistribution::Bag);
+    let strengths = bag
+        .players()
+        .flat_map(|p| PowerType::ALL.iter().map(move |&ptype| p.strength(ptype)))
+        .sorted()
+        .collect::<Vec<_>>();
+    let expected = (1..=5).cycle().take(18).sorted().collect::<Vec<_>>();
+    assert_eq!(strengths, expected);
+
+    // Both teams have three players.
+    let balanced = new_game(PowerDistribution::BalancedTeams);
+    let team_total = |team: &str| -> i16 {
+        balanced
+            .players()
+            .filter(|p| p.team == team)
+            .map(total)
+            .sum()
+    };
+    assert_eq!(team_total("Geats"), team_total("Danes"));
+}
+
+#[test]
+fn roles_follow_placement_rules() {
+    use crate::gamestate::rules::{ProphetPlacement, RolePlacement};
+
+    let setup = |seed, role_placement| {
+        test_setup(
+            seed,
+            Rules {
+                role_placement,
+                ..Default::default()
+            },
+        )
+        .add_player_or_panic("Luna")
+    };
+    for seed in 0..20 {
+        let game = setup(seed, Default::default()).finalize().unwrap();
+        let team_of_role = |wanted: fn(&Role) -> bool| {
+            game.players()
+                .filter(|p| p.role().is_some_and(wanted))
+                .map(|p| p.team.clone())
+                .collect::<Vec<_>>()
+        };
+        let destined = team_of_role(|r| matches!(r, Role::Destined));
+        let prophets = team_of_role(|r| matches!(r, Role::Prophet { .. }));
+        assert_eq!((destined.len(), prophets.len()), (1, 1));
+        assert_ne!(destined, prophets);
+        assert_eq!(team_of_role(|r| matches!(r, Role::Traitor)).len(), 1);
+    }
+
+    let impossible = RolePlacement {
+        prophets: ProphetPlacement::Any,
+        traitor_min_team_size: 5,
+    };
+    assert!(matches!(
+        setup(0, impossible).finalize(),
+        Err(StartGameErr::CannotPlaceTraitor(5))
+    ));
+}
+
+#[test]
+fn roles_are_dealt_whenever_placement_allows() {
+    use crate::gamestate::rules::{RoleComposition, RoleCounts};
+
+    // Dealing greedily would often put the destined players on both teams,
+    // leaving no prophet who is on neither's team.
+    let rules = Rules {
+        roles: RoleComposition::Fixed(RoleCounts {
+            min_players: 0,
+            destined: 2,
+            prophets: 1,
+            targets_per_prophet: 2,
+            traitors: 0,
+        }),
+        ..Default::default()
+    };
+    for seed in 0..50 {
+        let game = test_game(seed, rules.clone());
+        let destined = game
+            .players()
+            .filter(|p| matches!(p.role(), Some(Role::Destined)))
+            .collect::<Vec<_>>();
+        let prophet = game
+            .players()
+            .find(|p| matches!(p.role(), Some(Role::Prophet { .. })))
+            .unwrap();
+        assert_eq!(destined.len(), 2);
+        assert!(destined.iter().all(|p| p.team != prophet.team));
+    }
+}
+
+#[test]
+fn setup_names_can_be_edited() {
+    let mut setup = Setup::new_game()
+        .add_team_or_panic("Geats")
+        .add_team_or_panic("Danes")
+        .add_player_to_team_or_panic("Kyle", "Geats")
+        .add_player_or_panic("Lura");
+    setup.reserve_names(["quit"]);
+    assert!(matches!(
+        setup.add_player(" "),
+        Err(GameSetupErr::EmptyName)
+    ));
+    assert!(matches!(
+        setup.add_player("kyle"),
+        Err(GameSetupErr::NameCollides(..))
+    ));
+    assert!(matches!(
+        setup.add_team("Quit"),
+        Err(GameSetupErr::ReservedName(..))
+    ));
+    assert!(matches!(
+        setup.rename_team("Danes", "geats"),
+        Err(GameSetupErr::NameCollides(..))
+    ));
+    // Teams and players are named separately.
+    setup.rename_player("Lura", "Danes").unwrap();
+    setup.rename_player("Danes", "Laura").unwrap();
+
+    setup.rename_team("Geats", "geats").unwrap();
+    assert_eq!(
+        setup.players().collect::<Vec<_>>(),
+        [("Kyle", Some("geats")), ("Laura", None)]
+    );
+    setup.remove_team("geats").unwrap();
+    assert_eq!(setup.teams().collect::<Vec<_>>(), ["Danes"]);
+    assert_eq!(setup.players().next(), Some(("Kyle", None)));
+    assert!(matches!(
+        setup.remove_player("Geats"),
+        Err(GameSetupErr::PlayerNotFound)
+    ));
+}
@@ -0,0 +1,289 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use quick_error::quick_error;
+use serde::{Deserialize, Serialize};
+
+use crate::actions::attack::{AttackReport, AttackStep, DeclaredAttack, InvalidAttackErr, Side};
+use crate::gamestate::active::ActiveGame;
+use crate::gamestate::builder::{GameSetupErr, Setup, StartGameErr};
+use crate::gamestate::players::Player;
+use crate::gamestate::rules::{Rules, RulesErr};
+use crate::gamestate::teams::{AllianceErr, TName};
+use crate::gamestate::with_history::HistoryNavigationErr;
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum ReplayErr {
+        Io(err: io::Error) {
+            from()
+            display("Could not access log file: {}", err)
+        }
+        Format(err: serde_json::Error) {
+            from()
+            display("Invalid log: {}", err)
+        }
+        MissingSetup {
+            display("The log does not start with the game setup")
+        }
+        Rules(err: RulesErr) {
+            from()
+        }
+        Setup(err: GameSetupErr) {
+            from()
+        }
+        Start(err: StartGameErr) {
+            from()
+        }
+        Attack(err: InvalidAttackErr) {
+            from()
+        }
+        History(err: HistoryNavigationErr) {
+            from()
+        }
+        Alliance(err: AllianceErr) {
+            from()
+        }
+        UnexpectedEvent(index: usize) {
+            display("Event {} is out of place", index)
+        }
+        Diverged(index: usize) {
+            display("The replayed game differs from the log at event {}", index)
+        }
+    }
+}
+
+// Everything that happens in a game, in order. Events are only ever appended;
+// undoing an attack is itself an event.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct EventLog(Vec<GameEvent>);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum GameEvent {
+    Setup {
+        team_names: Vec<String>,
+        player_names: Vec<String>,
+        // Players placed on a particular team during setup
+        #[serde(default)]
+        pinned: BTreeMap<String, String>,
+        rules: Rules,
+        seed: u64,
+    },
+    // One per player: their team, tokens, role, and ward
+    Dealt(Player),
+    Attack(AttackStep),
+    // Ends each attack
+    Resolved(AttackReport),
+    // Alliances ended by an attack are part of its report instead.
+    Allied(TName, TName),
+    AllianceEnded(TName, TName),
+    Undo,
+    Redo,
+    Goto(usize),
+}
+
+impl EventLog {
+    pub(crate) fn record(&mut self, event: GameEvent) {
+        self.0.push(event);
+    }
+
+    pub fn events(&self) -> &[GameEvent] {
+        &self.0
+    }
+}
+
+pub fn save_file(log: &EventLog, path: impl AsRef<Path>) -> Result<(), ReplayErr> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(&mut writer, log)?;
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn load_file(path: impl AsRef<Path>) -> Result<EventLog, ReplayErr> {
+    Ok(serde_json::from_reader(BufReader::new(File::open(path)?))?)
+}
+
+// Rebuilds a game by repeating every logged action, then checks that doing so
+// reproduces the log exactly. Random rolls made during play are taken from the
+// log, so the rebuilt game's RNG does not match the original's.
+pub fn replay(log: &EventLog) -> Result<ActiveGame, ReplayErr> {
+    let mut events = log.events().iter().enumerate();
+    let mut game = match events.next() {
+        Some((
+            _,
+            GameEvent::Setup {
+                team_names,
+                player_names,
+                pinned,
+                rules,
+                seed,
+            },
+        )) => {
+            let mut setup = Setup::new_game().with_seed(*seed);
+            setup.set_rules(rules.clone())?;
+            for team in team_names {
+                setup.add_team(team)?;
+            }
+            for player in player_names {
+                match pinned.get(player) {
+                    Some(team) => setup.add_player_to_team(player, team)?,
+                    None => setup.add_player(player)?,
+                }
+            }
+            setup.finalize()?
+        }
+        _ => return Err(ReplayErr::MissingSetup),
+    };
+    while let Some((index, event)) = events.next() {
+        match event {
+            // Checked against the replayed game's own log below
+            GameEvent::Dealt(_) => {}
+            GameEvent::Attack(_) => {
+                let mut steps = vec![(index, event)];
+                let resolved = loop {
+                    match events.next() {
+                        Some((end, GameEvent::Resolved(_))) => break end,
+                        Some(step @ (_, GameEvent::Attack(_))) => steps.push(step),
+                        Some((index, _)) => return Err(ReplayErr::UnexpectedEvent(index)),
+                        None => return Err(ReplayErr::UnexpectedEvent(log.events().len())),
+                    }
+                };
+                replay_attack(&mut game, &steps, resolved)?;
+            }
+            GameEvent::Allied(a, b) => {
+                game.form_alliance(&a.0, &b.0)?;
+            }
+            GameEvent::AllianceEnded(a, b) => game.end_alliance(&a.0, &b.0)?,
+            GameEvent::Undo => game.undo()?,
+            GameEvent::Redo => game.redo()?,
+            GameEvent::Goto(node) => game.goto(*node)?,
+            GameEvent::Setup { .. } | GameEvent::Resolved(_) => {
+                return Err(ReplayErr::UnexpectedEvent(index))
+            }
+        }
+    }
+    check_matches(log, game.log())?;
+    Ok(game)
+}
+
+// `resolved` is the index of the event that ends the attack.
+fn replay_attack(
+    game: &mut ActiveGame,
+    steps: &[(usize, &GameEvent)],
+    resolved: usize,
+) -> Result<(), ReplayErr> {
+    let mut steps = steps.iter().map(|&(index, event)| match event {
+        GameEvent::Attack(step) => (index, step),
+        _ => unreachable!("Only attack steps are collected"),
+    });
+    let state = game.current_state();
+    let mut defense = match steps.next() {
+        Some((
+            _,
+            AttackStep::Declared {
+                attacker,
+                defender,
+                def_power,
+                for_team,
+            },
+        )) => DeclaredAttack::declare(
+            state,
+            attacker.as_str(),
+            defender.as_str(),
+            *def_power,
+            Some(&for_team.0),
+        )?,
+        Some((index, _)) => return Err(ReplayErr::UnexpectedEvent(index)),
+        None => unreachable!("Attacks start with a step"),
+    };
+    let mut offense = loop {
+        match steps.next() {
+            Some((_, AttackStep::Intercepted { protector })) => {
+                defense.intercept(protector.as_str())?
+            }
+            Some((_, AttackStep::DefenseColorChosen(def_power))) => {
+                defense.choose_def_power(*def_power)?
+            }
+            Some((
+                _,
+                AttackStep::Joined {
+                    player,
+                    side: Side::Defense,
+                    power,
+                },
+            )) => defense.add_with(player.as_str(), *power)?,
+            Some((_, AttackStep::AttackColorRolled(att_power))) => {
+                break defense.finalize_defense_with(*att_power)
+            }
+            Some((index, _)) => return Err(ReplayErr::UnexpectedEvent(index)),
+            None => return Err(ReplayErr::UnexpectedEvent(resolved)),
+        }
+    };
+    for (index, step) in steps {
+        match step {
+            AttackStep::Joined {
+                player,
+                side: Side::Attack,
+                power,
+            } => offense.add_with(player.as_str(), *power)?,
+            _ => return Err(ReplayErr::UnexpectedEvent(index)),
+        }
+    }
+    let attack = offense.finalize_offense();
+    game.apply_attack(attack);
+    Ok(())
+}
+
+// Compares events as JSON, since that is exactly what a log file records.
+fn check_matches(expected: &EventLog, replayed: &EventLog) -> Result<(), ReplayErr> {
+    let (expected, replayed) = (expected.events(), replayed.events());
+    for (index, (a, b)) in expected.iter().zip(replayed).enumerate() {
+        if serde_json::to_value(a)? != serde_json::to_value(b)? {
+            return Err(ReplayErr::Diverged(index));
+        }
+    }
+    if expected.len() != replayed.len() {
+        return Err(ReplayErr::Diverged(expected.len().min(replayed.len())));
+    }
+    Ok(())
+}
+
+#[test]
+fn replaying_a_log_rebuilds_the_game() {
+    use crate::gamestate::power::PowerType;
+    use crate::strategy::{automated_attack, StrategyKind};
+
+    let mut game = crate::gamestate::builder::test_game(11, Default::default());
+    let mut strategies = StrategyKind::GreedyHonor.for_everyone(game.current_state());
+    for _ in 0..3 {
+        let rules = game.rules().clone();
+        let (state, rng) = game.state_and_rng();
+        let attack = automated_attack(state, &rules, &mut strategies, rng).unwrap();
+        game.apply_attack(attack);
+    }
+    game.undo().unwrap();
+
+    let replayed = replay(game.log()).unwrap();
+    assert_eq!(
+        replayed.game_master_view().to_string(),
+        game.game_master_view().to_string()
+    );
+
+    // Tampering with a roll changes what follows.
+    let mut tampered = game.log().clone();
+    let roll = tampered
+        .0
+        .iter()
+        .position(|e| matches!(e, GameEvent::Attack(AttackStep::AttackColorRolled(_))))
+        .unwrap();
+    let GameEvent::Attack(AttackStep::AttackColorRolled(power)) = &mut tampered.0[roll] else {
+        unreachable!()
+    };
+    *power = match power {
+        PowerType::Red => PowerType::Blue,
+        _ => PowerType::Red,
+    };
+    assert!(replay(&tampered).is_err());
+}
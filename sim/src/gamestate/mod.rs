@@ -1,6 +1,12 @@
 pub mod active;
+pub mod assignment;
 pub mod builder;
+pub mod log;
 pub mod players;
 pub mod power;
+pub mod rules;
+pub mod save;
 pub mod teams;
+pub mod victory;
+pub mod view;
 pub mod with_history;
@@ -1,23 +1,64 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
 
-use crate::gamestate::power::{Power, PowerType};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::gamestate::active::GameRng;
+use crate::gamestate::builder::StartGameErr;
+use crate::gamestate::power::{Power, PowerType, NUM_POWER_TYPES};
+use crate::gamestate::teams::TeamsByName;
 
 // TODO move to separate file
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum Role {
-    Prophet { target: String },
+    // Succeeds if every target survives
+    Prophet {
+        // Older saves have a single `target`
+        #[serde(alias = "target", deserialize_with = "one_or_more")]
+        targets: Vec<String>,
+    },
     Traitor,
     Destined,
 }
 
-#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Debug)]
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Role::Prophet { targets } => {
+                write!(f, "Prophet (of the survival of {})", targets.join(", "))
+            }
+            Role::Traitor => write!(f, "Traitor"),
+            Role::Destined => write!(f, "Destined"),
+        }
+    }
+}
+
+fn one_or_more<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMore {
+        One(String),
+        More(Vec<String>),
+    }
+    Ok(match OneOrMore::deserialize(deserializer)? {
+        OneOrMore::One(name) => vec![name],
+        OneOrMore::More(names) => names,
+    })
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Debug, Serialize, Deserialize)]
 // This should *only* be constructed for known players. I.e., the existence of a
 // `PName` should guarantee the existence of a player with that name.
 // Note: this cannot actually be guaranteed as-is, since the active game-state
 // is not a singleton.
 pub struct PName(String);
 
+impl PName {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
 impl fmt::Display for PName {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         self.0.fmt(f)
@@ -30,16 +71,75 @@ impl PartialEq<&str> for PName {
     }
 }
 
+// Decides when a player who has lost power tokens is dead. A player with no
+// tokens left is always dead, regardless of the rule.
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
+pub enum DeathRule {
+    #[default]
+    LastTokenDiscarded,
+    TokensDiscarded(u8),
+}
+
+// Losing a fight wounds a token rather than discarding it outright. Only used
+// if the rules enable wounds.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WoundRule {
+    // How much each wound lowers the token's strength (never below zero)
+    pub penalty: i8,
+    // A token is discarded once it has this many wounds.
+    pub discard_at: u8,
+    // A player dies once they have this many wounds in total, however many
+    // tokens they have left. Otherwise, only the death rule applies.
+    pub fatal_at: Option<u8>,
+}
+
+impl Default for WoundRule {
+    fn default() -> Self {
+        WoundRule {
+            penalty: 1,
+            discard_at: 2,
+            fatal_at: None,
+        }
+    }
+}
+
+impl fmt::Display for WoundRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "-{} per wound, tokens discarded at {} wounds",
+            self.penalty, self.discard_at
+        )?;
+        if let Some(fatal) = self.fatal_at {
+            write!(f, ", fatal at {}", fatal)?;
+        }
+        Ok(())
+    }
+}
+
 pub trait PlayerAttributePool {
-    fn next_power(&mut self) -> Power;
-    // Roles are not independent, so the name is required
-    fn next_role(&mut self, name: &str) -> Option<Role>;
+    // Some distributions deal out power by team. `team` is `None` when power
+    // is dealt before teams are assigned (see `TeamAssignment::Balanced`).
+    fn next_power(&mut self, team: Option<&str>, rng: &mut GameRng) -> Power;
+    // Roles are constrained by team membership, so, like wards, they are
+    // dealt once every player has been placed on a team. Players missing from
+    // the map have no special role.
+    fn roles(
+        &mut self,
+        teams: &TeamsByName,
+        rng: &mut GameRng,
+    ) -> Result<HashMap<PName, Role>, StartGameErr>;
+    // Wards depend on team membership, so they can only be assigned once every
+    // player has been placed on a team. Maps each protector to their ward.
+    fn wards(&mut self, teams: &TeamsByName, rng: &mut GameRng) -> HashMap<PName, PName>;
 
     // Only for assertions or bookkeeping
     fn is_empty(&self) -> bool;
 }
 
-#[derive(Clone, Debug)]
+// `Debug` is implemented by hand so that secrets don't leak into logs.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Player {
     pub name: PName,
     pub team: String, // TODO should be TName
@@ -49,19 +149,59 @@ pub struct Player {
     // Roles are NEVER changed; they are ONLY used to (1) impact player behavior
     // and (2) determine victory conditions
     role: Option<Role>,
+    // The player this player is sworn to protect, even at the cost of their
+    // own life. Like the role, this is secret and never changes once assigned.
+    ward: Option<PName>,
+    // Per token, in `PowerType` order. Only used if the rules enable wounds.
+    #[serde(default)]
+    wounds: [u8; NUM_POWER_TYPES as usize],
+    alive: bool,
 }
 
 impl Player {
-    pub fn new(name: String, team: &str, attribute_pool: &mut dyn PlayerAttributePool) -> Player {
-        let role = attribute_pool.next_role(&name);
+    pub fn new(
+        name: String,
+        team: &str,
+        attribute_pool: &mut dyn PlayerAttributePool,
+        rng: &mut GameRng,
+    ) -> Player {
         Player {
             name: PName(name),
             team: team.to_owned(),
-            power: attribute_pool.next_power(),
-            role,
+            power: attribute_pool.next_power(Some(team), rng),
+            role: None,
+            ward: None,
+            wounds: Default::default(),
+            alive: true,
         }
     }
 
+    // Only for assigning teams by the power that was dealt; the team must be
+    // set before the game starts.
+    pub(crate) fn without_team(
+        name: String,
+        attribute_pool: &mut dyn PlayerAttributePool,
+        rng: &mut GameRng,
+    ) -> Player {
+        Player {
+            name: PName(name),
+            team: String::new(),
+            power: attribute_pool.next_power(None, rng),
+            role: None,
+            ward: None,
+            wounds: Default::default(),
+            alive: true,
+        }
+    }
+
+    // The sum of every remaining token's strength
+    pub fn total_strength(&self) -> i16 {
+        PowerType::ALL
+            .iter()
+            .map(|&ptype| i16::from(self.strength(ptype)))
+            .sum()
+    }
+
     pub fn strength(&self, ptype: PowerType) -> i8 {
         self.power[ptype].into()
     }
@@ -70,10 +210,82 @@ impl Player {
         self.power[ptype].nonempty()
     }
 
+    // Roles are secret; this should only be used for victory conditions and
+    // for automated player behavior.
+    pub fn role(&self) -> Option<&Role> {
+        self.role.as_ref()
+    }
+
+    // Secret, like the role.
+    pub fn ward(&self) -> Option<&PName> {
+        self.ward.as_ref()
+    }
+
+    // Only for use during game setup
+    pub fn assign_role(&mut self, role: Role) {
+        assert!(self.role.is_none(), "{} already has a role", self.name);
+        self.role = Some(role);
+    }
+
+    // Only for use during game setup
+    pub fn assign_ward(&mut self, ward: PName) {
+        assert!(self.ward.is_none(), "{} already has a ward", self.name);
+        assert!(ward != self.name, "{} cannot protect themselves", self.name);
+        self.ward = Some(ward);
+    }
+
     pub fn lose_power(&mut self, ptype: PowerType) {
         self.power[ptype].discard()
     }
 
+    pub fn wounds(&self, ptype: PowerType) -> u8 {
+        self.wounds[ptype as usize]
+    }
+
+    // Wider than each token's count, which can reach `discard_at`
+    pub fn total_wounds(&self) -> u16 {
+        self.wounds.iter().copied().map(u16::from).sum()
+    }
+
+    // Returns `true` if the wound was enough to discard the token.
+    pub fn wound(&mut self, ptype: PowerType, rule: WoundRule) -> bool {
+        self.power[ptype].weaken(rule.penalty);
+        self.wounds[ptype as usize] += 1;
+        if self.wounds[ptype as usize] >= rule.discard_at {
+            self.lose_power(ptype);
+            return true;
+        }
+        false
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.alive
+    }
+
+    // Returns `true` only if the player was alive and has now died.
+    pub fn apply_death_rule(&mut self, rule: DeathRule) -> bool {
+        if !self.alive {
+            return false;
+        }
+        let discarded = NUM_POWER_TYPES - self.power.remaining();
+        self.alive = match rule {
+            DeathRule::LastTokenDiscarded => !self.power.is_empty(),
+            DeathRule::TokensDiscarded(limit) => !self.power.is_empty() && discarded < limit,
+        };
+        !self.alive
+    }
+
+    // Like `apply_death_rule`, for players who have too many wounds.
+    pub fn apply_wound_rule(&mut self, rule: WoundRule) -> bool {
+        if !self.alive {
+            return false;
+        }
+        self.alive = rule
+            .fatal_at
+            .is_none_or(|fatal| self.total_wounds() < u16::from(fatal));
+        !self.alive
+    }
+
     pub fn pretty(player: &'_ Player) -> String {
         format!("{}", player)
     }
@@ -89,26 +301,76 @@ impl Player {
     }
 }
 
+impl fmt::Debug for Player {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Player")
+            .field("name", &self.name)
+            .field("team", &self.team)
+            .field("power", &self.power)
+            .field("role", &self.role.as_ref().map(|_| Secret))
+            .field("ward", &self.ward.as_ref().map(|_| Secret))
+            .field("wounds", &self.wounds)
+            .field("alive", &self.alive)
+            .finish()
+    }
+}
+
+struct Secret;
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<secret>")
+    }
+}
+
 // Does not included the team name (which can be printed separately) or the role
 // (which is secret and should not be printed)
 impl fmt::Display for Player {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        writeln!(f, "{:>12}: {}", self.name.0, self.power)
+        if self.alive {
+            write!(f, "{:>12}: {}", self.name.0, self.power)?;
+            let wounded = PowerType::ALL
+                .iter()
+                .filter(|&&ptype| self.has_power(ptype) && self.wounds(ptype) > 0)
+                .map(|&ptype| format!("{} x{}", ptype, self.wounds(ptype)))
+                .collect::<Vec<_>>();
+            if !wounded.is_empty() {
+                write!(f, "  (wounds: {})", wounded.join(", "))?;
+            }
+            writeln!(f)
+        } else {
+            writeln!(f, "{:>12}: (dead)", self.name.0)
+        }
     }
 }
 
-#[derive(Clone, Debug, Default)]
-pub struct PlayersByName(HashMap<PName, Player>);
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PlayersByName(BTreeMap<PName, Player>);
+
+impl FromIterator<Player> for PlayersByName {
+    fn from_iter<I: IntoIterator<Item = Player>>(players: I) -> Self {
+        PlayersByName(
+            players
+                .into_iter()
+                .map(|player| (player.name.clone(), player))
+                .collect(),
+        )
+    }
+}
 
 impl PlayersByName {
     pub fn from(
         team: &str,
         names: impl Iterator<Item = String>,
         attribute_pool: &mut dyn PlayerAttributePool,
+        rng: &mut GameRng,
     ) -> Self {
-        let mut map = HashMap::new();
+        let mut map = BTreeMap::new();
         for name in names {
-            map.insert(PName(name.clone()), Player::new(name, team, attribute_pool));
+            map.insert(
+                PName(name.clone()),
+                Player::new(name, team, attribute_pool, rng),
+            );
         }
         PlayersByName(map)
     }
@@ -125,3 +387,22 @@ impl PlayersByName {
         self.0.values_mut()
     }
 }
+
+#[test]
+fn wounds_add_up_past_a_single_token() {
+    let mut game = crate::gamestate::builder::test_game(0, Default::default());
+    let (kyle, _) = game.player_by_name("Kyle").unwrap();
+    let rule = WoundRule {
+        penalty: 0,
+        discard_at: 200,
+        fatal_at: None,
+    };
+    let player = game.player_mut(&kyle);
+    for &ptype in PowerType::ALL.iter() {
+        for _ in 0..100 {
+            assert!(!player.wound(ptype, rule));
+        }
+    }
+    assert_eq!(player.total_wounds(), 300);
+    assert!(!player.apply_wound_rule(rule));
+}
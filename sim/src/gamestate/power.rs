@@ -1,19 +1,27 @@
 use std::fmt;
 use std::ops::{Index, IndexMut};
+use std::str::FromStr;
 
 use colored::Color;
 use colored::Colorize;
 use rand::distributions::{Distribution, Uniform};
-use rand::rngs::ThreadRng;
+use rand::seq::SliceRandom;
+use rand::Rng;
 use rand_derive::Rand;
+use serde::{Deserialize, Serialize};
 
-#[derive(Copy, Clone, Debug, Rand)]
+use crate::gamestate::active::GameRng;
+use crate::gamestate::rules::RulesErr;
+
+#[derive(Copy, Clone, Debug, Rand, Serialize, Deserialize)]
 pub enum PowerType {
     Red = 0,
     Blue = 1,
     Green = 2,
 }
 
+pub const NUM_POWER_TYPES: u8 = 3;
+
 impl From<PowerType> for Color {
     fn from(pt: PowerType) -> Self {
         match pt {
@@ -30,16 +38,13 @@ impl fmt::Display for PowerType {
     }
 }
 
-// TODO DESIGN relative color advantage?
-const POWER_ADVANTAGE_MULTIPLYER: i16 = 2;
-
 impl PowerType {
-    // May be negative
-    pub fn relative_advantage(self, against: PowerType) -> i16 {
-        self.unit_advantage(against) * POWER_ADVANTAGE_MULTIPLYER
-    }
+    pub const ALL: [PowerType; NUM_POWER_TYPES as usize] =
+        [PowerType::Red, PowerType::Blue, PowerType::Green];
 
-    fn unit_advantage(self, against: PowerType) -> i16 {
+    // 1, 0, or -1; the actual bonus depends on the rules (see
+    // `Rules::advantage`).
+    pub fn unit_advantage(self, against: PowerType) -> i16 {
         /*
         Red beats Green, Green beats Blue, Blue beats Red:
         (self - against):
@@ -55,7 +60,7 @@ impl PowerType {
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 pub struct Power {
     red: ColorPower,
     blue: ColorPower,
@@ -63,15 +68,137 @@ pub struct Power {
 }
 
 impl Power {
-    pub fn randomize(power_range: Uniform<i8>, mut rng: &mut ThreadRng) -> Self {
+    pub fn new(red: i8, blue: i8, green: i8) -> Self {
+        Power {
+            red: ColorPower(Some(red)),
+            blue: ColorPower(Some(blue)),
+            green: ColorPower(Some(green)),
+        }
+    }
+
+    pub fn randomize(power_range: Uniform<i8>, rng: &mut GameRng) -> Self {
         Power {
-            red: ColorPower(Some(power_range.sample(&mut rng))),
-            green: ColorPower(Some(power_range.sample(&mut rng))),
-            blue: ColorPower(Some(power_range.sample(&mut rng))),
+            red: ColorPower(Some(power_range.sample(rng))),
+            green: ColorPower(Some(power_range.sample(rng))),
+            blue: ColorPower(Some(power_range.sample(rng))),
+        }
+    }
+}
+
+// How strengths are chosen for the power tokens dealt out at the start of the
+// game. Strengths always fall within the range set by the rules.
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
+pub enum PowerDistribution {
+    // Every token is rolled on its own, so one player may get 5/5/5 and
+    // another 1/1/1.
+    #[default]
+    Independent,
+    // Every strength in the range is equally common, and tokens are dealt out
+    // of a shuffled bag.
+    Bag,
+    // The strengths of each player's tokens add up to this total.
+    Budget(i16),
+    // Every team is dealt the same token sets (plus one more for teams with
+    // an extra player), so team totals match.
+    BalancedTeams,
+}
+
+impl PowerDistribution {
+    // For `BalancedTeams`, these are the sets to be copied for each team.
+    pub fn deal(self, num_players: usize, min: i8, max: i8, rng: &mut GameRng) -> Vec<Power> {
+        match self {
+            PowerDistribution::Independent | PowerDistribution::BalancedTeams => {
+                let range = Uniform::new_inclusive(min, max);
+                (0..num_players)
+                    .map(|_| Power::randomize(range, rng))
+                    .collect()
+            }
+            PowerDistribution::Bag => {
+                let mut bag = (min..=max)
+                    .cycle()
+                    .take(num_players * NUM_POWER_TYPES as usize)
+                    .collect::<Vec<_>>();
+                bag.shuffle(rng);
+                bag.chunks(NUM_POWER_TYPES as usize)
+                    .map(|t| Power::new(t[0], t[1], t[2]))
+                    .collect()
+            }
+            PowerDistribution::Budget(total) => (0..num_players)
+                .map(|_| Self::split_budget(total, min, max, rng))
+                .collect(),
+        }
+    }
+
+    // Starts every token at the minimum, then hands out the rest of the budget
+    // one point at a time.
+    fn split_budget(total: i16, min: i8, max: i8, rng: &mut GameRng) -> Power {
+        let mut tokens = [min; NUM_POWER_TYPES as usize];
+        let mut remaining = total - i16::from(min) * i16::from(NUM_POWER_TYPES);
+        while remaining > 0 {
+            let open = (0..tokens.len())
+                .filter(|&i| tokens[i] < max)
+                .collect::<Vec<_>>();
+            let index = open[rng.gen_range(0, open.len())];
+            tokens[index] += 1;
+            remaining -= 1;
+        }
+        Power::new(tokens[0], tokens[1], tokens[2])
+    }
+
+    // Only budgets that can be split within the power range are valid.
+    pub fn validate(self, min: i8, max: i8) -> Result<(), RulesErr> {
+        if let PowerDistribution::Budget(total) = self {
+            let tokens = i16::from(NUM_POWER_TYPES);
+            if total < i16::from(min) * tokens || total > i16::from(max) * tokens {
+                return Err(RulesErr::InvalidBudget(total));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for PowerDistribution {
+    type Err = RulesErr;
+
+    // "budget:<total>" for a per-player budget
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "independent" => Ok(PowerDistribution::Independent),
+            "bag" => Ok(PowerDistribution::Bag),
+            "balanced" => Ok(PowerDistribution::BalancedTeams),
+            _ => match s.strip_prefix("budget:").map(str::parse) {
+                Some(Ok(total)) => Ok(PowerDistribution::Budget(total)),
+                _ => Err(RulesErr::UnknownPowerDistribution(s.to_owned())),
+            },
         }
     }
 }
 
+impl fmt::Display for PowerDistribution {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PowerDistribution::Independent => write!(f, "independent"),
+            PowerDistribution::Bag => write!(f, "bag"),
+            PowerDistribution::Budget(total) => write!(f, "budget:{}", total),
+            PowerDistribution::BalancedTeams => write!(f, "balanced"),
+        }
+    }
+}
+
+impl Power {
+    pub fn is_empty(&self) -> bool {
+        self.remaining() == 0
+    }
+
+    // Number of tokens not yet discarded
+    pub fn remaining(&self) -> u8 {
+        [self.red, self.blue, self.green]
+            .iter()
+            .filter(|cp| cp.nonempty())
+            .count() as u8
+    }
+}
+
 impl fmt::Display for Power {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
@@ -85,7 +212,7 @@ impl fmt::Display for Power {
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 pub struct ColorPower(Option<i8>);
 
 impl ColorPower {
@@ -93,6 +220,10 @@ impl ColorPower {
         self.0 = None;
     }
 
+    pub fn weaken(&mut self, by: i8) {
+        self.0 = self.0.map(|strength| (strength - by).max(0));
+    }
+
     // TODO better name
     pub fn nonempty(self) -> bool {
         self.0.is_some()
@@ -108,7 +239,7 @@ impl ColorPower {
         format!(
             "{}{}{:3}", // Q: width works here but not above (in Power::fmt); why?
             "(".to_string().bold().color(color),
-            self.to_string(),
+            self,
             ")".to_string().bold().color(color),
         )
     }
@@ -149,13 +280,14 @@ impl IndexMut<PowerType> for Power {
 
 #[test]
 fn power_advantage() {
-    assert!(PowerType::Red.relative_advantage(PowerType::Red) == 0);
-    assert!(PowerType::Red.relative_advantage(PowerType::Green) == 2);
-    assert!(PowerType::Red.relative_advantage(PowerType::Blue) == -2);
-    assert!(PowerType::Green.relative_advantage(PowerType::Red) == -2);
-    assert!(PowerType::Green.relative_advantage(PowerType::Green) == 0);
-    assert!(PowerType::Green.relative_advantage(PowerType::Blue) == 2);
-    assert!(PowerType::Blue.relative_advantage(PowerType::Red) == 2);
-    assert!(PowerType::Blue.relative_advantage(PowerType::Green) == -2);
-    assert!(PowerType::Blue.relative_advantage(PowerType::Blue) == 0);
+    let rules = crate::gamestate::rules::Rules::default();
+    assert!(rules.advantage(PowerType::Red, PowerType::Red) == 0);
+    assert!(rules.advantage(PowerType::Red, PowerType::Green) == 2);
+    assert!(rules.advantage(PowerType::Red, PowerType::Blue) == -2);
+    assert!(rules.advantage(PowerType::Green, PowerType::Red) == -2);
+    assert!(rules.advantage(PowerType::Green, PowerType::Green) == 0);
+    assert!(rules.advantage(PowerType::Green, PowerType::Blue) == 2);
+    assert!(rules.advantage(PowerType::Blue, PowerType::Red) == 2);
+    assert!(rules.advantage(PowerType::Blue, PowerType::Green) == -2);
+    assert!(rules.advantage(PowerType::Blue, PowerType::Blue) == 0);
 }
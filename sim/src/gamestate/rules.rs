@@ -0,0 +1,637 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use itertools::Itertools;
+use quick_error::quick_error;
+use serde::{Deserialize, Serialize};
+
+use crate::actions::attack::{AttackResult, HonorAward};
+use crate::gamestate::players::{DeathRule, WoundRule};
+use crate::gamestate::power::{PowerDistribution, PowerType};
+use crate::gamestate::teams::TName;
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum RulesErr {
+        Io(err: io::Error) {
+            from()
+            display("Could not read rules file: {}", err)
+        }
+        Format(err: serde_json::Error) {
+            from()
+            display("Invalid rules: {}", err)
+        }
+        InvalidPowerRange(min: i8, max: i8) {
+            display("Invalid power range: {} to {}", min, max)
+        }
+        InvalidBudget(total: i16) {
+            display("A power budget of {} cannot be split within the power range", total)
+        }
+        UnknownPowerDistribution(name: String) {
+            display("Unknown power distribution: {} (expected independent, bag, budget:<total>, or balanced)", name)
+        }
+        InvalidRatio(ratio: f64) {
+            display("Cross-team ward ratio must be between 0 and 1, not {}", ratio)
+        }
+        NoPlayersPerTeam {
+            display("Teams must have at least one player")
+        }
+        InvalidProphetTargets(targets: usize, destined: usize) {
+            display("Prophets cannot each foretell {} of {} destined players", targets, destined)
+        }
+        UnknownTeamAssignment(name: String) {
+            display("Unknown team assignment: {} (expected random or balanced)", name)
+        }
+        BalancedTwice {
+            display("Teams cannot be balanced both by the power distribution and by assignment")
+        }
+        InvalidWounds {
+            display("Wounds cannot add strength, and tokens must be able to take at least one")
+        }
+        RoleTiersOutOfOrder {
+            display("Role counts must be listed in order of increasing player count")
+        }
+    }
+}
+
+// How much honor the winning team of an attack gains
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
+pub enum HonorRule {
+    // The total strength of the losing side
+    #[default]
+    LosingStrength,
+    // The total strength of the winning side, not counting the color advantage
+    WinningStrength,
+    Fixed(i16),
+}
+
+impl HonorRule {
+    pub fn honor_won(self, winning_strength: i16, losing_strength: i16) -> i16 {
+        match self {
+            HonorRule::LosingStrength => losing_strength,
+            HonorRule::WinningStrength => winning_strength,
+            HonorRule::Fixed(honor) => honor,
+        }
+    }
+}
+
+// How players who aren't pinned to a team are placed on one. Either way,
+// team sizes differ by at most one.
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
+pub enum TeamAssignment {
+    #[default]
+    Random,
+    // Power is dealt first, then teams are filled so that their total
+    // strength is as even as possible.
+    Balanced,
+}
+
+impl FromStr for TeamAssignment {
+    type Err = RulesErr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "random" => Ok(TeamAssignment::Random),
+            "balanced" => Ok(TeamAssignment::Balanced),
+            _ => Err(RulesErr::UnknownTeamAssignment(s.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for TeamAssignment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TeamAssignment::Random => write!(f, "random"),
+            TeamAssignment::Balanced => write!(f, "balanced"),
+        }
+    }
+}
+
+// Which teams get the honor a winning side gains, when players from several
+// teams fought on that side. The primary combatant counts for the team the
+// side fought for, and each assist for their own team.
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
+pub enum HonorSharing {
+    // All of it goes to the team the side fought for.
+    #[default]
+    SideTeam,
+    // Split by the strength each team's combatants committed
+    ByStrength,
+    // Split evenly between the teams that fought
+    Even,
+}
+
+impl HonorSharing {
+    // `contributions` holds the strength each winning combatant committed,
+    // along with the team it counts for. Honor that doesn't divide evenly goes
+    // to the side's team, whose award is always listed first.
+    pub fn split(
+        self,
+        honor: i16,
+        side_team: &TName,
+        contributions: &[(TName, i16)],
+    ) -> Vec<HonorAward> {
+        let mut weights = BTreeMap::new();
+        for (team, strength) in contributions {
+            let weight = weights.entry(team.clone()).or_insert(0);
+            match self {
+                HonorSharing::SideTeam => {}
+                HonorSharing::ByStrength => *weight += i32::from(*strength),
+                HonorSharing::Even => *weight = 1,
+            }
+        }
+        let total = weights.values().sum::<i32>();
+        let mut shares = Vec::new();
+        if total > 0 {
+            for (team, weight) in weights {
+                let share = (i32::from(honor) * weight / total) as i16;
+                if team != *side_team && share != 0 {
+                    shares.push(HonorAward { team, honor: share });
+                }
+            }
+        }
+        let given = shares.iter().map(|award| award.honor).sum::<i16>();
+        shares.insert(
+            0,
+            HonorAward {
+                team: side_team.clone(),
+                honor: honor - given,
+            },
+        );
+        shares
+    }
+}
+
+// How an attack is resolved when the two sides are evenly matched. Attack
+// strength includes the color advantage.
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
+pub enum TieRule {
+    // A tie is a failed attack.
+    #[default]
+    DefenderWins,
+    // Nobody loses a token or gains honor.
+    Stalemate,
+    // Nobody gains honor, but both primary combatants lose their tokens.
+    BothPrimariesLose,
+    // Any result closer than this is a stalemate, as above.
+    Margin(i16),
+}
+
+impl TieRule {
+    pub fn resolve(self, attack_strength: i16, defense_strength: i16) -> AttackResult {
+        let margin = attack_strength - defense_strength;
+        let stalemate = match self {
+            TieRule::DefenderWins => false,
+            TieRule::Stalemate | TieRule::BothPrimariesLose => margin == 0,
+            TieRule::Margin(threshold) => margin.abs() < threshold,
+        };
+        if stalemate {
+            AttackResult::Stalemate
+        } else if margin > 0 {
+            AttackResult::AttackerWon
+        } else {
+            AttackResult::DefenderWon
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoleCounts {
+    // Only used in a scaled composition: these counts apply to games with at
+    // least this many players.
+    pub min_players: usize,
+    pub destined: usize,
+    pub prophets: usize,
+    // How many of the destined players each prophet foretells the survival of
+    pub targets_per_prophet: usize,
+    pub traitors: usize,
+}
+
+impl Default for RoleCounts {
+    fn default() -> Self {
+        RoleCounts {
+            min_players: 0,
+            destined: 1,
+            prophets: 1,
+            targets_per_prophet: 1,
+            traitors: 1,
+        }
+    }
+}
+
+impl RoleCounts {
+    pub fn total(&self) -> usize {
+        self.destined + self.prophets + self.traitors
+    }
+
+    fn validate(&self) -> Result<(), RulesErr> {
+        if self.prophets > 0
+            && (self.targets_per_prophet == 0 || self.targets_per_prophet > self.destined)
+        {
+            return Err(RulesErr::InvalidProphetTargets(
+                self.targets_per_prophet,
+                self.destined,
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for RoleCounts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} destined, {} prophets (of {} each), {} traitors",
+            self.destined, self.prophets, self.targets_per_prophet, self.traitors
+        )
+    }
+}
+
+// Which special roles are dealt out. Rules files may give either a single set
+// of counts, or a list of them that scales with the number of players.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RoleComposition {
+    Fixed(RoleCounts),
+    // Sorted by `min_players`; the last one the game is large enough for
+    // applies. Smaller games have no special roles.
+    Scaled(Vec<RoleCounts>),
+}
+
+impl Default for RoleComposition {
+    fn default() -> Self {
+        RoleComposition::Scaled(vec![
+            Default::default(),
+            RoleCounts {
+                min_players: 10,
+                destined: 2,
+                prophets: 2,
+                ..Default::default()
+            },
+        ])
+    }
+}
+
+impl RoleComposition {
+    pub fn for_players(&self, num_players: usize) -> RoleCounts {
+        match self {
+            RoleComposition::Fixed(counts) => *counts,
+            RoleComposition::Scaled(tiers) => tiers
+                .iter()
+                .rev()
+                .find(|tier| tier.min_players <= num_players)
+                .copied()
+                .unwrap_or(RoleCounts {
+                    min_players: 0,
+                    destined: 0,
+                    prophets: 0,
+                    targets_per_prophet: 0,
+                    traitors: 0,
+                }),
+        }
+    }
+
+    fn validate(&self) -> Result<(), RulesErr> {
+        match self {
+            RoleComposition::Fixed(counts) => counts.validate(),
+            RoleComposition::Scaled(tiers) => {
+                if tiers
+                    .windows(2)
+                    .any(|pair| pair[0].min_players >= pair[1].min_players)
+                {
+                    return Err(RulesErr::RoleTiersOutOfOrder);
+                }
+                tiers.iter().try_for_each(RoleCounts::validate)
+            }
+        }
+    }
+}
+
+impl fmt::Display for RoleComposition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RoleComposition::Fixed(counts) => write!(f, "{}", counts),
+            RoleComposition::Scaled(tiers) => write!(
+                f,
+                "{}",
+                tiers
+                    .iter()
+                    .map(|tier| format!("{}+ players: {}", tier.min_players, tier))
+                    .join("; ")
+            ),
+        }
+    }
+}
+
+// Where prophets may be placed relative to the destined players they
+// foretell the survival of
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
+pub enum ProphetPlacement {
+    Any,
+    #[default]
+    OtherTeam,
+    SameTeam,
+}
+
+impl ProphetPlacement {
+    pub fn allows(self, prophet_team: &str, target_team: &str) -> bool {
+        match self {
+            ProphetPlacement::Any => true,
+            ProphetPlacement::OtherTeam => prophet_team != target_team,
+            ProphetPlacement::SameTeam => prophet_team == target_team,
+        }
+    }
+}
+
+// Constraints on which players may be dealt each role
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RolePlacement {
+    pub prophets: ProphetPlacement,
+    // Traitors are only placed on teams with at least this many players.
+    pub traitor_min_team_size: usize,
+}
+
+// The balance constants of the game. These can be loaded from a JSON file to
+// try out rule variants; any setting missing from the file keeps its default.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Rules {
+    // Bonus per unit of color advantage (e.g. red attacking green)
+    pub power_advantage_multiplier: i16,
+    // Every power token's strength is drawn uniformly from this range
+    // (inclusive).
+    pub min_power: i8,
+    pub max_power: i8,
+    pub power_distribution: PowerDistribution,
+    pub min_players_per_team: usize,
+    pub team_assignment: TeamAssignment,
+    pub roles: RoleComposition,
+    pub role_placement: RolePlacement,
+    pub honor: HonorRule,
+    pub honor_sharing: HonorSharing,
+    pub ties: TieRule,
+    pub death_rule: DeathRule,
+    // Without wounds, every lost fight discards a token outright.
+    pub wounds: Option<WoundRule>,
+    // The probability that a player's ward is on another team
+    pub cross_team_ward_ratio: f64,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            power_advantage_multiplier: 2,
+            min_power: 1,
+            max_power: 5,
+            power_distribution: Default::default(),
+            min_players_per_team: 3,
+            team_assignment: Default::default(),
+            roles: Default::default(),
+            role_placement: Default::default(),
+            honor: Default::default(),
+            honor_sharing: Default::default(),
+            ties: Default::default(),
+            death_rule: Default::default(),
+            wounds: None,
+            // "Usually" on the other team
+            cross_team_ward_ratio: 0.8,
+        }
+    }
+}
+
+impl Rules {
+    pub fn from_json(json: &str) -> Result<Rules, RulesErr> {
+        let rules: Rules = serde_json::from_str(json)?;
+        rules.validate()?;
+        Ok(rules)
+    }
+
+    pub fn load_file(path: impl AsRef<Path>) -> Result<Rules, RulesErr> {
+        Rules::from_json(&fs::read_to_string(path)?)
+    }
+
+    pub fn validate(&self) -> Result<(), RulesErr> {
+        if self.min_power > self.max_power {
+            return Err(RulesErr::InvalidPowerRange(self.min_power, self.max_power));
+        }
+        self.power_distribution
+            .validate(self.min_power, self.max_power)?;
+        if let (PowerDistribution::BalancedTeams, TeamAssignment::Balanced) =
+            (self.power_distribution, self.team_assignment)
+        {
+            return Err(RulesErr::BalancedTwice);
+        }
+        if !(0.0..=1.0).contains(&self.cross_team_ward_ratio) {
+            return Err(RulesErr::InvalidRatio(self.cross_team_ward_ratio));
+        }
+        if self.min_players_per_team == 0 {
+            return Err(RulesErr::NoPlayersPerTeam);
+        }
+        if let Some(wounds) = self.wounds {
+            if wounds.penalty < 0 || wounds.discard_at == 0 {
+                return Err(RulesErr::InvalidWounds);
+            }
+        }
+        self.roles.validate()
+    }
+
+    // The bonus (or, if negative, penalty) for attacking with one color
+    // against another
+    pub fn advantage(&self, attacking: PowerType, defending: PowerType) -> i16 {
+        attacking.unit_advantage(defending) * self.power_advantage_multiplier
+    }
+
+    // The bonus for one side of a fight: each combatant whose color beats any
+    // color the other side committed gains the advantage once. Only the
+    // favored side of a matchup gains anything, so in a one-on-one fight this
+    // is the same as a single bonus (or penalty) for the attacker.
+    pub fn side_advantage(
+        &self,
+        colors: impl IntoIterator<Item = PowerType>,
+        opposing: &[PowerType],
+    ) -> i16 {
+        let favored = colors
+            .into_iter()
+            .filter(|&color| opposing.iter().any(|&o| color.unit_advantage(o) > 0))
+            .count();
+        favored as i16 * self.power_advantage_multiplier
+    }
+}
+
+impl fmt::Display for Rules {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "Power {}-{} ({}), color advantage x{}, at least {} players per team ({} teams)",
+            self.min_power,
+            self.max_power,
+            self.power_distribution,
+            self.power_advantage_multiplier,
+            self.min_players_per_team,
+            self.team_assignment
+        )?;
+        writeln!(f, "Roles: {}", self.roles)?;
+        writeln!(
+            f,
+            "Prophets placed: {:?}; traitors on teams of at least {}",
+            self.role_placement.prophets, self.role_placement.traitor_min_team_size
+        )?;
+        write!(
+            f,
+            "Honor: {:?}, shared {:?}; ties: {:?}; death: {:?}; cross-team wards: {:.0}%",
+            self.honor,
+            self.honor_sharing,
+            self.ties,
+            self.death_rule,
+            100.0 * self.cross_team_ward_ratio
+        )?;
+        match &self.wounds {
+            Some(wounds) => write!(f, "\nWounds: {}", wounds),
+            None => write!(f, "\nWounds: off"),
+        }
+    }
+}
+
+#[test]
+fn partial_rules_files_keep_defaults() {
+    let rules = Rules::from_json(r#"{"max_power": 9, "roles": {"traitors": 2}}"#).unwrap();
+    assert_eq!(rules.max_power, 9);
+    assert_eq!(rules.min_power, 1);
+    assert_eq!(rules.roles.for_players(20).traitors, 2);
+    assert_eq!(rules.roles.for_players(20).destined, 1);
+
+    let scaled = Rules::from_json(
+        r#"{"roles": [{"traitors": 0}, {"min_players": 12, "destined": 3, "prophets": 2, "targets_per_prophet": 2}]}"#,
+    )
+    .unwrap();
+    assert_eq!(scaled.roles.for_players(11).traitors, 0);
+    assert_eq!(scaled.roles.for_players(12).destined, 3);
+    assert!(matches!(
+        Rules::from_json(r#"{"roles": {"destined": 1, "targets_per_prophet": 2}}"#),
+        Err(RulesErr::InvalidProphetTargets(2, 1))
+    ));
+    assert!(matches!(
+        Rules::from_json(r#"{"min_power": 4, "max_power": 3}"#),
+        Err(RulesErr::InvalidPowerRange(4, 3))
+    ));
+}
+
+#[test]
+fn tie_rules_decide_close_attacks() {
+    use AttackResult::*;
+
+    assert_eq!(TieRule::DefenderWins.resolve(5, 5), DefenderWon);
+    assert_eq!(TieRule::Stalemate.resolve(5, 5), Stalemate);
+    assert_eq!(TieRule::BothPrimariesLose.resolve(6, 5), AttackerWon);
+    assert_eq!(TieRule::Margin(2).resolve(6, 5), Stalemate);
+    assert_eq!(TieRule::Margin(2).resolve(5, 7), DefenderWon);
+    let rules = Rules::from_json(r#"{"ties": {"Margin": 3}}"#).unwrap();
+    assert!(matches!(rules.ties, TieRule::Margin(3)));
+}
+
+#[test]
+fn honor_is_shared_between_teams() {
+    let (geats, danes) = (TName("Geats".to_owned()), TName("Danes".to_owned()));
+    let contributions = [(geats.clone(), 5), (danes.clone(), 2), (geats.clone(), 1)];
+    let split = |sharing: HonorSharing| {
+        sharing
+            .split(10, &geats, &contributions)
+            .into_iter()
+            .map(|award| (award.team.0, award.honor))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(split(HonorSharing::SideTeam), [("Geats".to_owned(), 10)]);
+    // 10 * 2 / 8 rounds down; the remainder goes to the side's team.
+    assert_eq!(
+        split(HonorSharing::ByStrength),
+        [("Geats".to_owned(), 8), ("Danes".to_owned(), 2)]
+    );
+    assert_eq!(
+        split(HonorSharing::Even),
+        [("Geats".to_owned(), 5), ("Danes".to_owned(), 5)]
+    );
+}
+
+#[test]
+fn role_composition_is_dealt_and_checked() {
+    use crate::gamestate::builder::{test_game, test_setup, StartGameErr};
+    use crate::gamestate::players::Role;
+
+    let count_roles = |game: &crate::gamestate::active::ActiveGame| {
+        let mut counts = (0, 0, 0);
+        for player in game.players() {
+            match player.role() {
+                Some(Role::Destined) => counts.0 += 1,
+                Some(Role::Prophet { .. }) => counts.1 += 1,
+                Some(Role::Traitor) => counts.2 += 1,
+                None => {}
+            }
+        }
+        counts
+    };
+    // The default tiers add a destined player and a prophet at 10 players.
+    assert_eq!(count_roles(&test_game(0, Default::default())), (1, 1, 1));
+    let larger = test_setup(0, Default::default())
+        .add_player_or_panic("Luna")
+        .add_player_or_panic("Rosie")
+        .add_player_or_panic("Ida")
+        .add_player_or_panic("Beowulf")
+        .finalize()
+        .unwrap();
+    assert_eq!(count_roles(&larger), (2, 2, 1));
+
+    let fixed = |destined, prophets, targets_per_prophet, traitors| Rules {
+        roles: RoleComposition::Fixed(RoleCounts {
+            min_players: 0,
+            destined,
+            prophets,
+            targets_per_prophet,
+            traitors,
+        }),
+        ..Default::default()
+    };
+    let game = test_game(0, fixed(3, 1, 3, 0));
+    let destined = game
+        .players()
+        .filter(|p| matches!(p.role(), Some(Role::Destined)))
+        .map(|p| p.name.to_string())
+        .sorted()
+        .collect::<Vec<_>>();
+    let targets = game
+        .players()
+        .find_map(|p| match p.role() {
+            Some(Role::Prophet { targets }) => {
+                Some(targets.iter().cloned().sorted().collect::<Vec<_>>())
+            }
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(destined.len(), 3);
+    assert_eq!(targets, destined);
+
+    assert!(matches!(
+        test_setup(0, fixed(3, 3, 1, 1)).finalize(),
+        Err(StartGameErr::TooManyRoles(7, 6))
+    ));
+    let out_of_order = Rules {
+        roles: RoleComposition::Scaled(vec![
+            RoleCounts {
+                min_players: 10,
+                ..Default::default()
+            },
+            Default::default(),
+        ]),
+        ..Default::default()
+    };
+    let mut setup = test_setup(0, Default::default());
+    assert!(matches!(
+        setup.set_rules(out_of_order),
+        Err(RulesErr::RoleTiersOutOfOrder)
+    ));
+}
@@ -0,0 +1,187 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use quick_error::quick_error;
+use serde::{Deserialize, Serialize};
+
+use crate::gamestate::active::{ActiveGame, GameRng};
+use crate::gamestate::players::DeathRule;
+use crate::gamestate::rules::Rules;
+use crate::gamestate::with_history::GameHistory;
+
+// Bump this whenever a change to the game state (e.g. a rule change) would
+// prevent older saves from loading, and add a migration to `upgrade`.
+pub const SAVE_FORMAT_VERSION: u64 = 2;
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum SaveLoadErr {
+        Io(err: io::Error) {
+            from()
+            display("Could not access save file: {}", err)
+        }
+        Format(err: serde_json::Error) {
+            from()
+            display("Invalid save data: {}", err)
+        }
+        MissingVersion {
+            display("Save data has no format version")
+        }
+        UnsupportedVersion(version: u64) {
+            display("Save format version {} is not supported (current version: {})",
+                version, SAVE_FORMAT_VERSION)
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct SaveFile<'a> {
+    version: u64,
+    game: &'a ActiveGame,
+}
+
+// The version is read on its own first, since it determines how to read the
+// rest of the data.
+#[derive(Deserialize)]
+struct SaveVersion {
+    version: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct LoadFile<G> {
+    game: G,
+}
+
+// Version 1 predates configurable rules: only the death rule was saved, and
+// everything else was hard-coded to what are now the default rules.
+#[derive(Deserialize)]
+struct GameV1 {
+    history: GameHistory,
+    death_rule: DeathRule,
+    seed: u64,
+    rng: GameRng,
+}
+
+pub fn save_to(game: &ActiveGame, writer: impl Write) -> Result<(), SaveLoadErr> {
+    let save = SaveFile {
+        version: SAVE_FORMAT_VERSION,
+        game,
+    };
+    serde_json::to_writer_pretty(writer, &save)?;
+    Ok(())
+}
+
+pub fn load_from(mut reader: impl Read) -> Result<ActiveGame, SaveLoadErr> {
+    let mut data = String::new();
+    reader.read_to_string(&mut data)?;
+    let SaveVersion { version } = serde_json::from_str(&data)?;
+    upgrade(version.ok_or(SaveLoadErr::MissingVersion)?, &data)
+}
+
+pub fn save_file(game: &ActiveGame, path: impl AsRef<Path>) -> Result<(), SaveLoadErr> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    save_to(game, &mut writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn load_file(path: impl AsRef<Path>) -> Result<ActiveGame, SaveLoadErr> {
+    load_from(BufReader::new(File::open(path)?))
+}
+
+// Reads game data saved by any supported format version, converting older
+// formats to the current one.
+fn upgrade(version: u64, data: &str) -> Result<ActiveGame, SaveLoadErr> {
+    match version {
+        1 => {
+            let LoadFile::<GameV1> { game } = serde_json::from_str(data)?;
+            let rules = Rules {
+                death_rule: game.death_rule,
+                ..Default::default()
+            };
+            Ok(ActiveGame::from_parts(
+                game.history,
+                rules,
+                game.seed,
+                game.rng,
+            ))
+        }
+        SAVE_FORMAT_VERSION => {
+            let LoadFile::<ActiveGame> { game } = serde_json::from_str(data)?;
+            Ok(game)
+        }
+        _ => Err(SaveLoadErr::UnsupportedVersion(version)),
+    }
+}
+
+#[test]
+fn round_trip_preserves_history() {
+    use crate::actions::attack::DeclaredAttack;
+    use crate::gamestate::builder::test_game;
+    use crate::gamestate::power::PowerType;
+
+    let mut game = test_game(3, Default::default());
+    let attacker = game
+        .players()
+        .find(|p| p.team == "Danes")
+        .unwrap()
+        .name
+        .to_string();
+    let (state, rng) = game.state_and_rng();
+    let attack = DeclaredAttack::declare(state, &attacker, "Kyle", PowerType::Green, None)
+        .unwrap()
+        .finalize_defense(rng)
+        .finalize_offense();
+    game.apply_attack(attack);
+
+    let mut saved = Vec::new();
+    save_to(&game, &mut saved).unwrap();
+    let mut loaded = load_from(saved.as_slice()).unwrap();
+    assert_eq!(loaded.seed(), 3);
+    assert_eq!(
+        format!("{:?}", loaded.current_state()),
+        format!("{:?}", game.current_state())
+    );
+    assert_eq!(
+        loaded.game_master_view().to_string(),
+        game.game_master_view().to_string()
+    );
+    // The RNG state is saved too, so the game continues identically.
+    assert_eq!(
+        format!("{:?}", loaded.state_and_rng().1),
+        format!("{:?}", game.state_and_rng().1)
+    );
+    loaded.undo().unwrap();
+    assert!(loaded.undo().is_err());
+
+    // Older saves stored each attack directly, as the only kind of step.
+    let mut old: serde_json::Value = serde_json::from_slice(&saved).unwrap();
+    let history = &mut old["game"]["history"];
+    let data = &mut history["states"][0]["data"];
+    *data = data["Attack"].take();
+    let history: GameHistory = serde_json::from_value(history.take()).unwrap();
+    assert_eq!(
+        format!("{:?}", history.current_state()),
+        format!("{:?}", game.current_state())
+    );
+
+    let future = br#"{"version": 999, "game": {}}"#;
+    assert!(matches!(
+        load_from(&future[..]),
+        Err(SaveLoadErr::UnsupportedVersion(999))
+    ));
+}
+
+#[test]
+fn older_saves_keep_attack_results() {
+    use crate::actions::attack::AttackResult;
+
+    // Saved when attacks recorded only their result
+    let saved = include_str!("../../fixtures/save_v2_defender_won.json");
+    let game = load_from(saved.as_bytes()).unwrap();
+    let attack = game.step_at(1).unwrap().unwrap().attack().unwrap();
+    assert_eq!(attack.result(), Some(AttackResult::DefenderWon));
+    let report = attack.report().unwrap();
+    assert!(report.honor.is_empty() && report.losses.is_empty());
+}
@@ -1,9 +1,13 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt;
 
+use itertools::Itertools;
+use quick_error::quick_error;
+use serde::{Deserialize, Serialize};
+
 use crate::gamestate::players::{PName, Player, PlayersByName};
 
-#[derive(PartialEq, Eq, Hash, Clone, Debug)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Debug, Serialize, Deserialize)]
 // TODO Like PName, the intent was to make the inner `String` private and
 // only permit creating TNames from this interface.
 // For now, just punt on this.
@@ -12,10 +16,32 @@ use crate::gamestate::players::{PName, Player, PlayersByName};
 // this module, only references to valid TNames could be acquired.
 pub struct TName(pub String);
 
-#[derive(Clone, Debug, Default)]
+quick_error! {
+    #[derive(Debug)]
+    pub enum AllianceErr {
+        TeamNotFound(team: String) {
+            display("No team named {}", team)
+        }
+        SameTeam {
+            display("A team cannot ally with itself")
+        }
+        AlreadyAllied {
+            display("Those teams are already allied")
+        }
+        NotAllied {
+            display("Those teams are not allied")
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct Team {
     players: PlayersByName,
     honor: i16,
+    // Alliances are public, and last until either team ends them or attacks
+    // the other. Older saves have none.
+    #[serde(default)]
+    allies: BTreeSet<TName>,
 }
 
 impl Team {
@@ -23,6 +49,18 @@ impl Team {
         self.honor += honor;
     }
 
+    pub fn honor(&self) -> i16 {
+        self.honor
+    }
+
+    pub fn players(&self) -> impl Iterator<Item = &Player> {
+        self.players.players()
+    }
+
+    pub fn allies(&self) -> impl Iterator<Item = &TName> {
+        self.allies.iter()
+    }
+
     pub fn pretty_players(&self) -> String {
         Player::pretty_multi(self.players.players())
     }
@@ -31,19 +69,33 @@ impl Team {
 impl fmt::Display for Team {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         writeln!(f, "  Honor: {}", self.honor)?;
+        if !self.allies.is_empty() {
+            writeln!(
+                f,
+                "  Allies: {}",
+                self.allies.iter().map(|t| &t.0).join(", ")
+            )?;
+        }
         // TODO: Indent the 'pretty_players' string
         write!(f, "{}", self.pretty_players())
     }
 }
 
-#[derive(Clone, Debug, Default)]
-pub struct TeamsByName(HashMap<TName, Team>);
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct TeamsByName(BTreeMap<TName, Team>);
 
 impl TeamsByName {
     pub fn add(&mut self, t: &str, players: PlayersByName) {
         if self
             .0
-            .insert(TName(t.to_owned()), Team { players, honor: 0 })
+            .insert(
+                TName(t.to_owned()),
+                Team {
+                    players,
+                    honor: 0,
+                    allies: Default::default(),
+                },
+            )
             .is_some()
         {
             panic!("Team name added twice: {}", t)
@@ -56,6 +108,60 @@ impl TeamsByName {
         self.team_mut(t).gain_honor(honor);
     }
 
+    pub fn teams(&self) -> impl Iterator<Item = (&TName, &Team)> {
+        self.0.iter()
+    }
+
+    pub fn team_by_name(&self, name: &str) -> Option<&TName> {
+        self.0.keys().find(|t| t.0 == name)
+    }
+
+    pub fn are_allied(&self, a: &TName, b: &TName) -> bool {
+        self.0.get(a).is_some_and(|team| team.allies.contains(b))
+    }
+
+    // True for teammates as well as allies
+    pub fn are_friendly(&self, a: &str, b: &str) -> bool {
+        a == b
+            || self
+                .team_by_name(a)
+                .zip(self.team_by_name(b))
+                .is_some_and(|(a, b)| self.are_allied(a, b))
+    }
+
+    pub fn form_alliance(&mut self, a: &str, b: &str) -> Result<(TName, TName), AllianceErr> {
+        let (a, b) = self.alliance_pair(a, b)?;
+        if self.are_allied(&a, &b) {
+            return Err(AllianceErr::AlreadyAllied);
+        }
+        self.team_mut(&a).allies.insert(b.clone());
+        self.team_mut(&b).allies.insert(a.clone());
+        Ok((a, b))
+    }
+
+    pub fn end_alliance(&mut self, a: &str, b: &str) -> Result<(TName, TName), AllianceErr> {
+        let (a, b) = self.alliance_pair(a, b)?;
+        if !self.are_allied(&a, &b) {
+            return Err(AllianceErr::NotAllied);
+        }
+        self.team_mut(&a).allies.remove(&b);
+        self.team_mut(&b).allies.remove(&a);
+        Ok((a, b))
+    }
+
+    fn alliance_pair(&self, a: &str, b: &str) -> Result<(TName, TName), AllianceErr> {
+        let find = |name: &str| {
+            self.team_by_name(name)
+                .cloned()
+                .ok_or_else(|| AllianceErr::TeamNotFound(name.to_owned()))
+        };
+        let (a, b) = (find(a)?, find(b)?);
+        if a == b {
+            return Err(AllianceErr::SameTeam);
+        }
+        Ok((a, b))
+    }
+
     pub fn player_by_name(&self, name: &str) -> Option<(PName, TName)> {
         self.players()
             .find(|p| p.name == name)
@@ -91,16 +197,16 @@ impl TeamsByName {
     }
 
     pub fn players(&self) -> impl Iterator<Item = &Player> {
-        self.0.iter().flat_map(|(_, team)| team.players.players())
+        self.0.values().flat_map(|team| team.players.players())
     }
 
     pub fn players_mut(&mut self) -> impl Iterator<Item = &mut Player> {
         self.0
-            .iter_mut()
-            .flat_map(|(_, team)| team.players.players_mut())
+            .values_mut()
+            .flat_map(|team| team.players.players_mut())
     }
 
-    pub fn pretty_player<'a>(&self, name: &'a PName) -> String {
+    pub fn pretty_player(&self, name: &PName) -> String {
         Player::pretty(self.player_data(name))
     }
 
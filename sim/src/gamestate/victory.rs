@@ -0,0 +1,318 @@
+use std::fmt;
+
+use itertools::Itertools;
+
+use crate::gamestate::players::{PName, Role};
+use crate::gamestate::teams::{TName, TeamsByName};
+
+// The game is over once every team with any player left alive is allied with
+// every other such team; in particular, once no more than one is left.
+pub fn is_over(state: &TeamsByName) -> bool {
+    state
+        .teams()
+        .filter(|(_, team)| team.players().any(|p| p.is_alive()))
+        .map(|(name, _)| name)
+        .collect::<Vec<_>>()
+        .into_iter()
+        .tuple_combinations()
+        .all(|(a, b)| state.are_allied(a, b))
+}
+
+pub fn evaluate(state: &TeamsByName) -> GameResult {
+    let mut standings = state
+        .teams()
+        .map(|(name, team)| Standing {
+            rank: 0,
+            team: name.clone(),
+            honor: team.honor(),
+            survivors: team.players().filter(|p| p.is_alive()).count(),
+            allies: team.allies().cloned().collect(),
+        })
+        .collect::<Vec<_>>();
+    // Highest honor first; ties are broken by name only so that the ordering is stable.
+    standings.sort_by(|a, b| b.honor.cmp(&a.honor).then_with(|| a.team.0.cmp(&b.team.0)));
+    // Teams with equal honor share a rank, and the ranks after them are
+    // skipped (1, 1, 3).
+    for index in 0..standings.len() {
+        standings[index].rank = match index {
+            0 => 1,
+            _ if standings[index].honor == standings[index - 1].honor => standings[index - 1].rank,
+            _ => index + 1,
+        };
+    }
+    let winners = standings
+        .iter()
+        .filter(|standing| standing.rank == 1)
+        .map(|standing| standing.team.clone())
+        .collect::<Vec<_>>();
+
+    let mut destined = Vec::new();
+    let mut prophets = Vec::new();
+    let mut traitors = Vec::new();
+    for player in state.players().sorted_by(|a, b| a.name.cmp(&b.name)) {
+        match player.role() {
+            Some(Role::Destined) => destined.push((player.name.clone(), player.is_alive())),
+            Some(Role::Prophet { targets }) => prophets.push(ProphetResult {
+                prophet: player.name.clone(),
+                targets: targets.clone(),
+                // A target that isn't in the game can't have been kept alive.
+                succeeded: targets.iter().all(|target| {
+                    state
+                        .player_by_name(target)
+                        .is_some_and(|(pname, _)| state.player_data(&pname).is_alive())
+                }),
+            }),
+            Some(Role::Traitor) => {
+                let team = TName(player.team.clone());
+                traitors.push(TraitorResult {
+                    traitor: player.name.clone(),
+                    // The traitor's goal is for their own team *not* to win.
+                    succeeded: !winners.contains(&team),
+                    team,
+                })
+            }
+            None => {}
+        }
+    }
+
+    GameResult {
+        all_destined_survived: destined.iter().all(|(_, survived)| *survived),
+        standings,
+        winners,
+        destined,
+        prophets,
+        traitors,
+    }
+}
+
+#[derive(Debug)]
+pub struct GameResult {
+    // Every team, highest honor first
+    pub standings: Vec<Standing>,
+    // More than one team only in the case of a tie
+    pub winners: Vec<TName>,
+    pub destined: Vec<(PName, bool)>,
+    // If any 'destined' player died, the world is doomed, regardless of honor.
+    pub all_destined_survived: bool,
+    pub prophets: Vec<ProphetResult>,
+    pub traitors: Vec<TraitorResult>,
+}
+
+#[derive(Debug)]
+pub struct Standing {
+    pub rank: usize,
+    pub team: TName,
+    pub honor: i16,
+    // Players still alive
+    pub survivors: usize,
+    // Alliances still standing at the end of the game
+    pub allies: Vec<TName>,
+}
+
+impl fmt::Display for Standing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:>3}. {:>12}: {} honor, {} alive",
+            self.rank, self.team.0, self.honor, self.survivors
+        )?;
+        if !self.allies.is_empty() {
+            write!(
+                f,
+                " (allied with {})",
+                self.allies.iter().map(|t| &t.0).join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct ProphetResult {
+    pub prophet: PName,
+    pub targets: Vec<String>,
+    pub succeeded: bool,
+}
+
+#[derive(Debug)]
+pub struct TraitorResult {
+    pub traitor: PName,
+    pub team: TName,
+    pub succeeded: bool,
+}
+
+fn succeeded_or_failed(succeeded: bool) -> &'static str {
+    if succeeded {
+        "succeeded"
+    } else {
+        "failed"
+    }
+}
+
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Game over!")?;
+        writeln!(
+            f,
+            "Winner(s) by honor: {}",
+            self.winners.iter().map(|t| &t.0).join(", ")
+        )?;
+        for standing in &self.standings {
+            writeln!(f, "{}", standing)?;
+        }
+        writeln!(
+            f,
+            "Destined: {}",
+            self.destined
+                .iter()
+                .map(|(name, survived)| format!(
+                    "{} ({})",
+                    name,
+                    if *survived { "survived" } else { "died" }
+                ))
+                .join(", ")
+        )?;
+        if self.all_destined_survived {
+            writeln!(f, "Every destined player survived; the future is saved.")?;
+        } else {
+            writeln!(f, "A destined player died; the world is doomed.")?;
+        }
+        for prophet in &self.prophets {
+            writeln!(
+                f,
+                "Prophet {} (protecting {}) {}",
+                prophet.prophet,
+                prophet.targets.join(", "),
+                succeeded_or_failed(prophet.succeeded)
+            )?;
+        }
+        for traitor in &self.traitors {
+            writeln!(
+                f,
+                "Traitor {} (betraying team {}) {}",
+                traitor.traitor,
+                traitor.team.0,
+                succeeded_or_failed(traitor.succeeded)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn game_ends_when_one_team_is_dead() {
+    use crate::gamestate::builder::test_game;
+    use crate::gamestate::power::PowerType;
+
+    let mut game = test_game(0, Default::default());
+    assert!(game.result().is_none());
+
+    for player in game.players_mut().filter(|p| p.team == "Danes") {
+        for ptype in [PowerType::Red, PowerType::Blue, PowerType::Green] {
+            player.lose_power(ptype);
+        }
+        assert!(player.apply_death_rule(Default::default()));
+    }
+    let result = game.result().expect("Game should be over");
+    assert_eq!(result.standings.len(), 2);
+    assert_eq!(result.destined.len(), 1);
+    assert_eq!(
+        result.all_destined_survived,
+        game.players()
+            .filter(|p| matches!(p.role(), Some(Role::Destined)))
+            .all(|p| p.team == "Geats")
+    );
+}
+
+#[test]
+fn allied_survivors_end_the_game() {
+    use crate::actions::attack::{AttackReport, AttackResult, DeclaredAttack};
+    use crate::gamestate::active::ActiveGame;
+    use crate::gamestate::builder::Setup;
+    use crate::gamestate::power::PowerType;
+    use crate::gamestate::rules::{HonorRule, Rules};
+
+    // Has the first living players of the two teams fight, with whichever
+    // colors let the attacker win.
+    fn win(game: &mut ActiveGame, attacking: &str, defending: &str) -> AttackReport {
+        let [attacker, defender] = [attacking, defending].map(|team| {
+            game.players()
+                .find(|p| p.team == team && p.is_alive())
+                .unwrap()
+                .name
+                .to_string()
+        });
+        let outcome = PowerType::ALL
+            .into_iter()
+            .cartesian_product(PowerType::ALL)
+            .filter_map(|(def_power, att_power)| {
+                let attack = DeclaredAttack::declare(
+                    game.current_state(),
+                    &attacker,
+                    &defender,
+                    def_power,
+                    None,
+                )
+                .ok()?
+                .finalize_defense_with(att_power)
+                .finalize_offense();
+                Some(game.preview(attack))
+            })
+            .find(|outcome| outcome.report().unwrap().result == AttackResult::AttackerWon)
+            .expect("Some choice of colors should win");
+        let report = outcome.report().unwrap().clone();
+        assert!(game.apply_attack_outcome(outcome).is_none());
+        report
+    }
+
+    let mut game = Setup::new_game()
+        .with_seed(5)
+        .with_rules(Rules {
+            honor: HonorRule::Fixed(3),
+            ..Default::default()
+        })
+        .add_team_or_panic("Geats")
+        .add_team_or_panic("Danes")
+        .add_team_or_panic("Swedes")
+        .add_player_or_panic("Kyle")
+        .add_player_or_panic("Laura")
+        .add_player_or_panic("Brandon")
+        .add_player_or_panic("Suzie")
+        .add_player_or_panic("Lauren")
+        .add_player_or_panic("Annabelle")
+        .add_player_or_panic("Luna")
+        .add_player_or_panic("Rosie")
+        .add_player_or_panic("Beowulf")
+        .finalize()
+        .unwrap();
+    assert!(game.form_alliance("Geats", "Danes").unwrap().is_none());
+    assert!(game.form_alliance("Danes", "Geats").is_err());
+
+    // Attacking an ally ends the alliance.
+    let report = win(&mut game, "Geats", "Danes");
+    assert!(report.ended_alliance.is_some());
+    assert!(!game.current_state().are_friendly("Geats", "Danes"));
+    win(&mut game, "Danes", "Swedes");
+
+    for player in game.players_mut().filter(|p| p.team == "Swedes") {
+        for ptype in PowerType::ALL {
+            player.lose_power(ptype);
+        }
+        player.apply_death_rule(Default::default());
+    }
+    assert!(game.result().is_none());
+    let result = game
+        .form_alliance("Geats", "Danes")
+        .unwrap()
+        .expect("Only allies are left");
+    let standings = result
+        .standings
+        .iter()
+        .map(|s| (s.rank, s.team.0.as_str(), s.honor, s.allies.len()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        standings,
+        [(1, "Danes", 3, 1), (1, "Geats", 3, 1), (3, "Swedes", 0, 0)]
+    );
+    assert_eq!(result.winners.len(), 2);
+}
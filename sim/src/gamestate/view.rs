@@ -0,0 +1,221 @@
+use std::fmt;
+
+use crate::actions::attack::DeclaredAttack;
+use crate::actions::defense::{self, DefenseOption};
+use crate::gamestate::players::{PName, Player, Role};
+use crate::gamestate::power::PowerType;
+use crate::gamestate::rules::Rules;
+use crate::gamestate::teams::{TName, TeamsByName};
+
+// Everything a single player is allowed to know about the game: their own
+// secrets, plus the rules and the public state (power tokens, life, and honor)
+// of everyone.
+pub struct PlayerView<'a> {
+    state: &'a TeamsByName,
+    rules: &'a Rules,
+    me: &'a Player,
+}
+
+impl<'a> PlayerView<'a> {
+    // Panics if the player does not exist (see `TeamsByName::player_data`)
+    pub fn new(state: &'a TeamsByName, rules: &'a Rules, player: &PName) -> Self {
+        PlayerView {
+            state,
+            rules,
+            me: state.player_data(player),
+        }
+    }
+
+    pub fn rules(&self) -> &'a Rules {
+        self.rules
+    }
+
+    pub fn name(&self) -> &'a PName {
+        &self.me.name
+    }
+
+    pub fn team(&self) -> &'a str {
+        &self.me.team
+    }
+
+    // True for this player's own team and its allies
+    pub fn is_friendly(&self, team: &str) -> bool {
+        self.state.are_friendly(self.team(), team)
+    }
+
+    pub fn role(&self) -> Option<&'a Role> {
+        self.me.role()
+    }
+
+    pub fn ward(&self) -> Option<&'a PName> {
+        self.me.ward()
+    }
+
+    pub fn me(&self) -> PublicPlayer<'a> {
+        PublicPlayer(self.me)
+    }
+
+    pub fn player(&self, name: &PName) -> PublicPlayer<'a> {
+        PublicPlayer(self.state.player_data(name))
+    }
+
+    pub fn players(&self) -> impl Iterator<Item = PublicPlayer<'a>> {
+        self.state.players().map(PublicPlayer)
+    }
+
+    // This player's defense colors against the given attacker, best first.
+    // Fights are resolved from public information only.
+    pub fn rank_defenses(&self, attacker: &PName) -> Vec<DefenseOption> {
+        let declared = PowerType::ALL
+            .iter()
+            .find(|&&power| self.me.has_power(power))
+            .and_then(|&power| {
+                DeclaredAttack::declare(
+                    self.state,
+                    attacker.as_str(),
+                    self.name().as_str(),
+                    power,
+                    None,
+                )
+                .ok()
+            });
+        match declared {
+            Some(declared) => defense::rank_defenses(&declared, self.rules, &[]),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn honor(&self) -> impl Iterator<Item = (&'a TName, i16)> {
+        self.state.teams().map(|(name, team)| (name, team.honor()))
+    }
+}
+
+// Shows only what this player knows: their secrets, then the public state.
+impl fmt::Display for PlayerView<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "You are {}, on team {}.", self.name(), self.team())?;
+        match self.role() {
+            Some(role) => writeln!(f, "Your role: {}", role)?,
+            None => writeln!(f, "You have no special role.")?,
+        }
+        match self.ward() {
+            Some(ward) => writeln!(f, "You are sworn to protect {}.", ward)?,
+            None => writeln!(f, "You are not sworn to protect anyone.")?,
+        }
+        write!(f, "{}", self.state)
+    }
+}
+
+// Everything, including every player's secrets; only for whoever is running
+// the game (or for reviewing it afterward)
+pub struct GameMasterView<'a> {
+    state: &'a TeamsByName,
+}
+
+impl<'a> GameMasterView<'a> {
+    pub fn new(state: &'a TeamsByName) -> Self {
+        GameMasterView { state }
+    }
+
+    pub fn player(&self, name: &PName) -> &'a Player {
+        self.state.player_data(name)
+    }
+
+    pub fn players(&self) -> impl Iterator<Item = &'a Player> {
+        self.state.players()
+    }
+}
+
+impl fmt::Display for GameMasterView<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, team) in self.state.teams() {
+            writeln!(f, "Team {} (honor {}):", name.0, team.honor())?;
+            for player in team.players() {
+                // `Player`'s own formatting includes a newline.
+                write!(f, "{}", player)?;
+                writeln!(
+                    f,
+                    "{:>14}role: {}; sworn to protect: {}",
+                    "",
+                    player.role().map_or("none".to_owned(), Role::to_string),
+                    player.ward().map_or("nobody".to_owned(), PName::to_string),
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// The parts of a player that every other player can see
+#[derive(Clone, Copy)]
+pub struct PublicPlayer<'a>(&'a Player);
+
+impl<'a> PublicPlayer<'a> {
+    pub fn name(&self) -> &'a PName {
+        &self.0.name
+    }
+
+    pub fn team(&self) -> &'a str {
+        &self.0.team
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.0.is_alive()
+    }
+
+    pub fn strength(&self, ptype: PowerType) -> i8 {
+        self.0.strength(ptype)
+    }
+
+    pub fn has_power(&self, ptype: PowerType) -> bool {
+        self.0.has_power(ptype)
+    }
+}
+
+#[test]
+fn views_reveal_only_what_each_player_knows() {
+    let game = crate::gamestate::builder::test_game(0, Default::default());
+    let secrets = |player: &Player| {
+        (
+            player.role().map_or("none".to_owned(), Role::to_string),
+            player.ward().map_or("nobody".to_owned(), PName::to_string),
+        )
+    };
+    let role_names = ["Prophet", "Traitor", "Destined"];
+
+    let debug = format!("{:?}", game);
+    let display = format!("{}", game);
+    assert!(role_names
+        .iter()
+        .all(|role| !debug.contains(role) && !display.contains(role)));
+    // Every role and ward in the `Debug` output is either absent or hidden.
+    for field in ["role", "ward"] {
+        let count = |shown: &str| debug.matches(&format!("{}: {}", field, shown)).count();
+        assert!(count("Some(<secret>)") > 0);
+        assert_eq!(count("Some(<secret>)") + count("None"), count(""));
+    }
+    assert!(!display.contains("protect"));
+    // The seed and RNG state would reveal all of the above.
+    assert!(!debug.contains("seed") && !debug.contains("rng") && !debug.contains("Pcg"));
+
+    for player in game.players() {
+        let view = game.view_for(&player.name).to_string();
+        let (role, ward) = secrets(player);
+        match player.role() {
+            Some(_) => assert!(view.contains(&format!("Your role: {}", role))),
+            None => assert!(view.contains("You have no special role.")),
+        }
+        assert!(view.contains(&format!("You are sworn to protect {}.", ward)));
+        assert_eq!(view.matches("protect").count(), 1);
+        for other in game.players().filter(|other| other.role().is_some()) {
+            let other_role = secrets(other).0;
+            assert!(other_role == role || !view.contains(&other_role));
+        }
+    }
+
+    let game_master = game.game_master_view().to_string();
+    for player in game.players() {
+        let (role, ward) = secrets(player);
+        assert!(game_master.contains(&format!("role: {}; sworn to protect: {}", role, ward)));
+    }
+}
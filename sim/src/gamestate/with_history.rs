@@ -1,27 +1,102 @@
 use crate::actions::attack::AttackOutcome;
-use crate::gamestate::teams::TeamsByName;
+use crate::gamestate::teams::{TName, TeamsByName};
 
 use quick_error::quick_error;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fmt;
 
 quick_error! {
     #[derive(Debug)]
     pub enum HistoryNavigationErr {
         UndoFromInitialState {}
+        NothingToRedo {}
+        NoSuchNode(node: usize) {
+            display("There is no game state numbered {}", node)
+        }
     }
 }
 
-#[derive(Debug)]
+// History is a tree: undoing a step and then taking a different one starts a
+// new branch, but the old branch remains reachable.
+//
+// Outside of this module, game states are identified by node numbers: 0 is the
+// initial state, and each step ever taken gets the next number.
+#[derive(Debug, Serialize, Deserialize)]
 pub struct GameHistory {
     initial_state: TeamsByName,
     states: Vec<GameStateNode>,
     current: Option<usize>,
+    // Where `redo` goes from the initial state
+    #[serde(default)]
+    initial_next: Option<usize>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 struct GameStateNode {
-    data: AttackOutcome,
+    #[serde(deserialize_with = "step")]
+    data: HistoryStep,
     previous: Option<usize>,
-    // next: Option<usize>, // ignore for now
+    // The most recently visited child; this is where `redo` goes.
+    #[serde(default)]
+    next: Option<usize>,
+}
+
+// Whatever led from one game state to the next. Alliances change between
+// attacks, so they are steps of their own.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum HistoryStep {
+    Attack(Box<AttackOutcome>),
+    Allied(TName, TName, TeamsByName),
+    AllianceEnded(TName, TName, TeamsByName),
+}
+
+// Older saves only have attacks.
+fn step<'de, D: Deserializer<'de>>(deserializer: D) -> Result<HistoryStep, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Step {
+        Any(HistoryStep),
+        Attack(Box<AttackOutcome>),
+    }
+    Ok(match Step::deserialize(deserializer)? {
+        Step::Any(step) => step,
+        Step::Attack(attack) => HistoryStep::Attack(attack),
+    })
+}
+
+impl HistoryStep {
+    pub fn new_state(&self) -> &TeamsByName {
+        match self {
+            HistoryStep::Attack(attack) => &attack.new_state,
+            HistoryStep::Allied(_, _, state) | HistoryStep::AllianceEnded(_, _, state) => state,
+        }
+    }
+
+    fn new_state_mut(&mut self) -> &mut TeamsByName {
+        match self {
+            HistoryStep::Attack(attack) => &mut attack.new_state,
+            HistoryStep::Allied(_, _, state) | HistoryStep::AllianceEnded(_, _, state) => state,
+        }
+    }
+
+    pub fn attack(&self) -> Option<&AttackOutcome> {
+        match self {
+            HistoryStep::Attack(attack) => Some(attack),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for HistoryStep {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HistoryStep::Attack(attack) => write!(f, "{}", attack.attack()),
+            HistoryStep::Allied(a, b, _) => write!(f, "Teams {} and {} ally", a.0, b.0),
+            HistoryStep::AllianceEnded(a, b, _) => {
+                write!(f, "Teams {} and {} end their alliance", a.0, b.0)
+            }
+        }
+    }
 }
 
 impl GameHistory {
@@ -30,32 +105,35 @@ impl GameHistory {
             initial_state,
             states: Vec::new(),
             current: None,
+            initial_next: None,
         }
     }
 
     pub fn current_state(&self) -> &TeamsByName {
         match self.current {
-            Some(index) => &self.node(index).data.new_state,
+            Some(index) => self.node(index).data.new_state(),
             None => &self.initial_state,
         }
     }
 
     pub fn current_mut(&mut self) -> &mut TeamsByName {
         match self.current {
-            Some(index) => &mut self.node_mut(index).data.new_state,
+            Some(index) => self.node_mut(index).data.new_state_mut(),
             None => &mut self.initial_state,
         }
     }
 
-    pub fn apply_attack(&mut self, attack: AttackOutcome) {
+    pub fn apply(&mut self, step: HistoryStep) {
         self.states.push(GameStateNode {
-            data: attack,
+            data: step,
             previous: self.current,
+            next: None,
         });
-        self.current = Some(self.states.len() - 1)
+        self.current = Some(self.states.len() - 1);
+        self.mark_path_to_current();
     }
 
-    pub fn undo_last_attack(&mut self) -> Result<(), HistoryNavigationErr> {
+    pub fn undo(&mut self) -> Result<(), HistoryNavigationErr> {
         let previous = match self.current {
             Some(index) => self.node(index).previous,
             None => return Err(HistoryNavigationErr::UndoFromInitialState),
@@ -64,6 +142,94 @@ impl GameHistory {
         Ok(())
     }
 
+    pub fn redo(&mut self) -> Result<(), HistoryNavigationErr> {
+        let next = match self.current {
+            Some(index) => self.node(index).next,
+            None => self.initial_next,
+        };
+        self.current = Some(next.ok_or(HistoryNavigationErr::NothingToRedo)?);
+        Ok(())
+    }
+
+    pub fn current_node(&self) -> usize {
+        Self::node_number(self.current)
+    }
+
+    // The steps that have been taken from the current state, i.e. the
+    // different lines of play that branch from here, as node numbers
+    pub fn branches(&self) -> Vec<usize> {
+        self.states
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| node.previous == self.current)
+            .map(|(index, _)| Self::node_number(Some(index)))
+            .collect()
+    }
+
+    // The attacks leading from the initial state to the current one, in order
+    pub fn attacks_so_far(&self) -> Vec<&AttackOutcome> {
+        let mut attacks = Vec::new();
+        let mut index = self.current;
+        while let Some(i) = index {
+            attacks.extend(self.node(i).data.attack());
+            index = self.node(i).previous;
+        }
+        attacks.reverse();
+        attacks
+    }
+
+    // The node `redo` would go to
+    pub fn redo_target(&self) -> Option<usize> {
+        let next = match self.current {
+            Some(index) => self.node(index).next,
+            None => self.initial_next,
+        };
+        next.map(|index| Self::node_number(Some(index)))
+    }
+
+    // The step that produced the given state; `None` for the initial state
+    pub fn step_at(&self, node: usize) -> Result<Option<&HistoryStep>, HistoryNavigationErr> {
+        Ok(self.node_index(node)?.map(|index| &self.node(index).data))
+    }
+
+    pub fn goto(&mut self, node: usize) -> Result<(), HistoryNavigationErr> {
+        self.current = self.node_index(node)?;
+        self.mark_path_to_current();
+        Ok(())
+    }
+
+    // Makes `redo` from any earlier state on this branch lead back here.
+    fn mark_path_to_current(&mut self) {
+        let mut child = match self.current {
+            Some(index) => index,
+            None => return,
+        };
+        loop {
+            match self.node(child).previous {
+                Some(parent) => {
+                    self.node_mut(parent).next = Some(child);
+                    child = parent;
+                }
+                None => {
+                    self.initial_next = Some(child);
+                    return;
+                }
+            }
+        }
+    }
+
+    fn node_number(index: Option<usize>) -> usize {
+        index.map_or(0, |index| index + 1)
+    }
+
+    fn node_index(&self, node: usize) -> Result<Option<usize>, HistoryNavigationErr> {
+        match node {
+            0 => Ok(None),
+            n if n <= self.states.len() => Ok(Some(n - 1)),
+            n => Err(HistoryNavigationErr::NoSuchNode(n)),
+        }
+    }
+
     fn node(&self, index: usize) -> &GameStateNode {
         &self.states[index]
     }
@@ -1,18 +1,30 @@
 use ultlib::actions::attack::{AddAttacker, AddDefender, Attack, DeclaredAttack};
-use ultlib::gamestate::{active::ActiveGame, builder::Setup, power::PowerType};
+use ultlib::actions::defense;
+use ultlib::gamestate::active::{ActiveGame, GameRng};
+use ultlib::gamestate::{
+    builder::{Setup, StartGameErr},
+    log,
+    power::PowerType,
+    rules::Rules,
+    save,
+    teams::TeamsByName,
+};
+use ultlib::strategy::{self, StrategyKind};
 
+use itertools::Itertools;
 use quick_error::quick_error;
 use shrust::{ExecError, Shell, ShellIO};
 use std::io::prelude::*;
 use std::io::BufReader;
 
-// TODO Eventually, there will need to be a way to show information to certain
-// players but not others.
-
-pub fn run() {
-    let game = setup_game();
+// Fails if setup ends (e.g. at the end of input) before the game can start.
+pub fn run(seed: Option<u64>, rules: Rules) -> Result<(), StartGameErr> {
+    let game = setup_game(seed, rules)?;
+    println!("Game seed: {}", game.seed());
+    println!("Rules:\n{}", game.rules());
     println!("{}", &game);
     play(game);
+    Ok(())
 }
 
 quick_error! {
@@ -20,6 +32,9 @@ quick_error! {
     enum InteractiveError {
         InvalidColorType {}
         PlayerDoesNotExist(name: String) {}
+        ExpectedTeamOrPlayer(word: String) {
+            display("Expected 'team' or 'player', not {}", word)
+        }
     }
 }
 
@@ -27,33 +42,224 @@ fn play(mut game: ActiveGame) {
     let mut shell = Shell::new(&mut game);
     shell.new_command(
         "attack",
-        "Initiate a new attack; arg1: attacker, arg2: defender",
+        "Initiate a new attack; arg1: attacker, arg2: defender, arg3 (optional): team to fight for",
         2,
         |io, game, s| {
-            let declared = declare_attack(game, s, io)?;
-            let attack = add_combatants(declared)?;
+            let rules = game.rules().clone();
+            let (state, rng) = game.state_and_rng();
+            let declared = declare_attack(state, &rules, s, io)?;
+            let attack = add_combatants(declared, &rules, rng)?;
 
             writeln!(io, "About to apply: {}", &attack)?;
-
-            game.apply_attack(attack);
-
+            apply_attack(io, game, attack)
+        },
+    );
+    shell.new_command(
+        "auto",
+        "Let automated players make the next attack; arg1 (optional): random, greedy, or role-aware",
+        0,
+        |io, game, s| {
+            let kind = match s.first() {
+                Some(name) => name
+                    .parse::<StrategyKind>()
+                    .map_err(|e| ExecError::Other(Box::new(e)))?,
+                None => StrategyKind::RoleAware,
+            };
+            let mut strategies = kind.for_everyone(game.current_state());
+            let rules = game.rules().clone();
+            let (state, rng) = game.state_and_rng();
+            let attack = match strategy::automated_attack(state, &rules, &mut strategies, rng) {
+                Some(attack) => attack,
+                None => {
+                    writeln!(io, "Nobody wants to attack.")?;
+                    return Ok(());
+                }
+            };
+            writeln!(io, "About to apply: {}", &attack)?;
+            apply_attack(io, game, attack)
+        },
+    );
+    shell.new_command("state", "Show the game state", 0, |io, game, _s| {
+        writeln!(io, "{}", game)?;
+        Ok(())
+    });
+    shell.new_command(
+        "whoami",
+        "Show only what one player knows, including their secrets; arg1: player",
+        1,
+        |io, game, s| {
+            ensure_player_exists(game.current_state(), s[0])?;
+            let (name, _) = game.player_by_name(s[0]).expect("Player exists");
+            writeln!(io, "{}", game.view_for(&name))?;
+            Ok(())
+        },
+    );
+    shell.new_command(
+        "gm",
+        "Show everything, including every player's secrets",
+        0,
+        |io, game, _s| {
+            writeln!(io, "{}", game.game_master_view())?;
+            Ok(())
+        },
+    );
+    shell.new_command(
+        "switches",
+        "List every attacker who has fought for another team",
+        0,
+        |io, game, _s| {
+            let mut any = false;
+            for switch in game.side_switches() {
+                writeln!(io, "{}", switch)?;
+                any = true;
+            }
+            if !any {
+                writeln!(io, "Nobody has fought for another team.")?;
+            }
+            Ok(())
+        },
+    );
+    shell.new_command(
+        "ally",
+        "Declare an alliance between two teams; arg1: team, arg2: team",
+        2,
+        |io, game, s| {
+            let result = game.form_alliance(s[0], s[1])?;
+            writeln!(io, "Teams {} and {} are now allied", s[0], s[1])?;
+            if let Some(result) = result {
+                writeln!(io, "{}", result)?;
+                return Err(ExecError::Quit);
+            }
+            Ok(())
+        },
+    );
+    shell.new_command(
+        "unally",
+        "End an alliance between two teams; arg1: team, arg2: team",
+        2,
+        |io, game, s| {
+            game.end_alliance(s[0], s[1])?;
+            writeln!(io, "Teams {} and {} are no longer allied", s[0], s[1])?;
+            Ok(())
+        },
+    );
+    shell.new_command(
+        "undo",
+        "Undo the last attack or alliance change",
+        0,
+        |io, game, _s| {
+            game.undo()?;
             writeln!(io, "{}", game)?;
             Ok(())
         },
     );
-    shell.new_command("undo", "Undo last attack", 0, |io, game, _s| {
-        game.undo_last_attack()?;
+    shell.new_command("redo", "Redo the last undone step", 0, |io, game, _s| {
+        game.redo()?;
         writeln!(io, "{}", game)?;
         Ok(())
     });
-    shell.set_prompt("Playing! Start a new attack, undo last attack, or quit: ".into());
+    shell.new_command(
+        "branches",
+        "List the attacks and alliance changes that have been played from this point",
+        0,
+        |io, game, _s| {
+            let branches = game.branches();
+            if branches.is_empty() {
+                writeln!(io, "Nothing has been played from here.")?;
+            }
+            for node in branches {
+                let marker = if game.redo_target() == Some(node) {
+                    "*"
+                } else {
+                    " "
+                };
+                let step = game
+                    .step_at(node)?
+                    .expect("Branches are never the initial state");
+                writeln!(io, "{} {}: {}", marker, node, step)?;
+            }
+            writeln!(io, "Current position: {}", game.current_node())?;
+            Ok(())
+        },
+    );
+    shell.new_command(
+        "goto",
+        "Jump to a numbered game state (0 is the start of the game); arg1: number",
+        1,
+        |io, game, s| {
+            let node = s[0]
+                .parse::<usize>()
+                .map_err(|e| ExecError::Other(Box::new(e)))?;
+            game.goto(node)?;
+            writeln!(io, "{}", game)?;
+            Ok(())
+        },
+    );
+    shell.new_command(
+        "save",
+        "Save the game to a file; arg1: file",
+        1,
+        |io, game, s| {
+            save::save_file(game, s[0]).map_err(|e| ExecError::Other(Box::new(e)))?;
+            writeln!(io, "Saved game to {}", s[0])?;
+            Ok(())
+        },
+    );
+    shell.new_command(
+        "log",
+        "Write everything that has happened this game to a file, for replaying; arg1: file",
+        1,
+        |io, game, s| {
+            log::save_file(game.log(), s[0]).map_err(|e| ExecError::Other(Box::new(e)))?;
+            writeln!(io, "Wrote {} events to {}", game.log().events().len(), s[0])?;
+            Ok(())
+        },
+    );
+    shell.new_command(
+        "load",
+        "Replace the current game with one loaded from a file; arg1: file",
+        1,
+        |io, game, s| {
+            **game = save::load_file(s[0]).map_err(|e| ExecError::Other(Box::new(e)))?;
+            writeln!(io, "Loaded game with seed {}", game.seed())?;
+            writeln!(io, "{}", game)?;
+            Ok(())
+        },
+    );
+    shell.set_prompt(
+        "Playing! Start a new or automated attack, show the state, whoami, gm, switches, ally, unally, undo, redo, list branches, goto, save, log, load, or quit: ".into(),
+    );
 
     prompt(shell);
     println!("Final game state: {}", &game);
 }
 
-fn setup_game() -> ActiveGame {
-    let mut setup = Setup::new_game();
+fn apply_attack(io: &mut ShellIO, game: &mut ActiveGame, attack: Attack) -> Result<(), ExecError> {
+    let outcome = game.preview(attack);
+    write!(io, "{}", outcome)?;
+    let result = game.apply_attack_outcome(outcome);
+
+    if let Some(result) = result {
+        writeln!(io, "{}", result)?;
+        return Err(ExecError::Quit);
+    }
+    Ok(())
+}
+
+// Every command of the shells in this module, including the ones `shrust`
+// provides; names are arguments to commands, so none may be used as one.
+const KEYWORDS: [&str; 26] = [
+    "ally", "attack", "attacker", "auto", "branches", "defender", "gm", "goto", "help", "history",
+    "list", "load", "log", "odds", "player", "quit", "redo", "remove", "rename", "save", "state",
+    "switches", "team", "unally", "undo", "whoami",
+];
+
+fn setup_game(seed: Option<u64>, rules: Rules) -> Result<ActiveGame, StartGameErr> {
+    let mut setup = Setup::new_game().with_rules(rules);
+    setup.reserve_names(KEYWORDS);
+    if let Some(seed) = seed {
+        setup.set_seed(seed);
+    }
     let mut shell = Shell::new(&mut setup);
     shell.new_command("team", "Add a new team", 1, |io, setup, s| {
         setup
@@ -62,21 +268,99 @@ fn setup_game() -> ActiveGame {
         writeln!(io, "{}", &setup)?;
         Ok(())
     });
-    shell.new_command("player", "Add a new player", 1, |io, setup, s| {
-        setup
-            .add_player(s[0])
+    shell.new_command(
+        "player",
+        "Add a new player; arg2 (optional): team to put them on",
+        1,
+        |io, setup, s| {
+            match s.get(1) {
+                Some(team) => setup.add_player_to_team(s[0], team),
+                None => setup.add_player(s[0]),
+            }
             .map_err(|e| ExecError::Other(Box::new(e)))?;
-        writeln!(io, "{}", &setup)?;
-        Ok(())
-    });
-    shell.set_prompt("Add new team or player name, or 'quit' to finish setup:".into());
+            writeln!(io, "{}", &setup)?;
+            Ok(())
+        },
+    );
+    shell.new_command(
+        "remove",
+        "Remove a team or player; arg1: 'team' or 'player', arg2: name",
+        2,
+        |io, setup, s| {
+            match s[0] {
+                "team" => setup.remove_team(s[1]),
+                "player" => setup.remove_player(s[1]),
+                _ => return Err(team_or_player(s[0])),
+            }
+            .map_err(|e| ExecError::Other(Box::new(e)))?;
+            writeln!(io, "{}", &setup)?;
+            Ok(())
+        },
+    );
+    shell.new_command(
+        "rename",
+        "Rename a team or player; arg1: 'team' or 'player', arg2: current name, arg3: new name",
+        3,
+        |io, setup, s| {
+            match s[0] {
+                "team" => setup.rename_team(s[1], s[2]),
+                "player" => setup.rename_player(s[1], s[2]),
+                _ => return Err(team_or_player(s[0])),
+            }
+            .map_err(|e| ExecError::Other(Box::new(e)))?;
+            writeln!(io, "{}", &setup)?;
+            Ok(())
+        },
+    );
+    shell.new_command(
+        "list",
+        "List the teams and players so far",
+        0,
+        |io, setup, _s| {
+            for team in setup.teams() {
+                let pinned = setup
+                    .players()
+                    .filter(|&(_, pinned_to)| pinned_to == Some(team))
+                    .map(|(name, _)| name)
+                    .join(", ");
+                writeln!(io, "Team {}: {}", team, pinned)?;
+            }
+            let unplaced = setup
+                .players()
+                .filter(|(_, team)| team.is_none())
+                .map(|(name, _)| name)
+                .join(", ");
+            writeln!(io, "Not yet on a team: {}", unplaced)?;
+            Ok(())
+        },
+    );
+    // Replaces the built-in `quit`, so that a setup the game can't start from
+    // (e.g. too many players pinned to one team) can still be fixed.
+    shell.new_command(
+        "quit",
+        "Finish setup and start the game",
+        0,
+        |_io, setup, _s| match setup.clone().finalize() {
+            Ok(_) => Err(ExecError::Quit),
+            Err(e) => Err(ExecError::Other(Box::new(e))),
+        },
+    );
+    shell.set_prompt(
+        "Add a team or player (players may be followed by their team), remove or rename a team or player, list, or 'quit' to finish setup:".into(),
+    );
 
     prompt(shell);
-    setup.finalize().expect("Could not initialize game")
+    setup.finalize()
+}
+
+fn team_or_player(word: &str) -> ExecError {
+    ExecError::Other(Box::new(InteractiveError::ExpectedTeamOrPlayer(
+        word.to_owned(),
+    )))
 }
 
-fn ensure_player_exists(game: &ActiveGame, name: &str) -> Result<(), ExecError> {
-    if game.player_by_name(name).is_none() {
+fn ensure_player_exists(state: &TeamsByName, name: &str) -> Result<(), ExecError> {
+    if state.player_by_name(name).is_none() {
         Err(ExecError::Other(Box::new(
             InteractiveError::PlayerDoesNotExist(name.to_owned()),
         )))
@@ -86,65 +370,162 @@ fn ensure_player_exists(game: &ActiveGame, name: &str) -> Result<(), ExecError>
 }
 
 fn declare_attack<'a>(
-    game: &'a ActiveGame,
+    state: &'a TeamsByName,
+    rules: &Rules,
     s: &[&str],
-    mut io: &mut ShellIO,
+    io: &mut ShellIO,
 ) -> Result<AddDefender<'a>, ExecError> {
-    ensure_player_exists(game, s[0])?;
-    ensure_player_exists(game, s[1])?;
+    ensure_player_exists(state, s[0])?;
+    ensure_player_exists(state, s[1])?;
 
+    let declare = |power_type| {
+        DeclaredAttack::declare(state, s[0], s[1], power_type, s.get(2).copied())
+            .map_err(|e| ExecError::Other(Box::new(e)))
+    };
+    // The ranking tries every color, so any color the defender holds will do.
+    let (defender, _) = state.player_by_name(s[1]).expect("Player exists");
+    let held = PowerType::ALL
+        .iter()
+        .copied()
+        .find(|&power| state.player_data(&defender).has_power(power))
+        .unwrap_or(PowerType::Red);
+    show_defenses(io, &declare(held)?, rules)?;
     writeln!(io, "Choose defense color (red > green > blue):")?;
-    let mut reader = BufReader::new(&mut io);
-    let mut color_input = String::new();
-    reader.read_line(&mut color_input)?;
-    let power_type = match color_input.trim().to_lowercase().as_str() {
-        "red" => PowerType::Red,
-        "green" => PowerType::Green,
-        "blue" => PowerType::Blue,
-        _ => {
-            return Err(ExecError::Other(Box::new(
-                InteractiveError::InvalidColorType,
-            )))
+    let power_type = parse_power_type(&read_line(io)?)?;
+    let mut declared = declare(power_type)?;
+
+    // Wards are secret, so this is asked whether or not anyone is sworn to
+    // protect the defender.
+    writeln!(
+        io,
+        "Anyone sworn to protect {} may take their place; enter your name, or nothing to let them defend:",
+        defender
+    )?;
+    let protector = read_line(io)?;
+    if !protector.trim().is_empty() {
+        declared
+            .intercept(protector.trim())
+            .map_err(|e| ExecError::Other(Box::new(e)))?;
+        let protector = declared.attack.targeted_defender().clone();
+        writeln!(io, "{} steps in to protect {}!", protector, defender)?;
+        show_defenses(io, &declared, rules)?;
+        writeln!(
+            io,
+            "Choose {}'s defense color, or nothing to keep {}:",
+            protector, power_type
+        )?;
+        let color_input = read_line(io)?;
+        if !color_input.trim().is_empty() {
+            declared
+                .choose_def_power(parse_power_type(&color_input)?)
+                .map_err(|e| ExecError::Other(Box::new(e)))?;
         }
-    };
+    }
+    Ok(declared)
+}
 
-    DeclaredAttack::declare(game.current_state(), s[0], s[1], power_type)
-        .map_err(|e| ExecError::Other(Box::new(e)))
+// Ranks the targeted defender's colors for the attack as declared so far.
+fn show_defenses(io: &mut ShellIO, declared: &AddDefender, rules: &Rules) -> Result<(), ExecError> {
+    let defender = declared.attack.targeted_defender();
+    writeln!(io, "Defense colors for {}, best first:", defender)?;
+    for option in defense::rank_defenses(declared, rules, &[]) {
+        writeln!(io, "  {}", option)?;
+    }
+    Ok(())
 }
 
-fn add_combatants(declared: AddDefender) -> Result<Attack, ExecError> {
-    let defense_ready = add_defenders(declared)?;
-    add_attackers(defense_ready)
+fn read_line(io: &mut ShellIO) -> Result<String, ExecError> {
+    let mut input = String::new();
+    BufReader::new(io).read_line(&mut input)?;
+    Ok(input)
 }
 
-fn add_defenders(mut declared: AddDefender<'_>) -> Result<AddAttacker, ExecError> {
+fn parse_power_type(input: &str) -> Result<PowerType, ExecError> {
+    match input.trim().to_lowercase().as_str() {
+        "red" => Ok(PowerType::Red),
+        "green" => Ok(PowerType::Green),
+        "blue" => Ok(PowerType::Blue),
+        _ => Err(ExecError::Other(Box::new(
+            InteractiveError::InvalidColorType,
+        ))),
+    }
+}
+
+fn add_combatants(
+    declared: AddDefender,
+    rules: &Rules,
+    rng: &mut GameRng,
+) -> Result<Attack, ExecError> {
+    let defense_ready = add_defenders(declared, rules, rng)?;
+    add_attackers(defense_ready, rules)
+}
+
+fn add_defenders<'a>(
+    mut declared: AddDefender<'a>,
+    rules: &Rules,
+    rng: &mut GameRng,
+) -> Result<AddAttacker<'a>, ExecError> {
     println!("Adding defenders to {}", &declared);
     let mut shell = Shell::new(&mut declared);
-    shell.new_command("defender", "Add a defender", 1, |io, declared, s| {
-        declared
-            .add(s[0])
+    shell.new_command(
+        "defender",
+        "Add a defender; arg1: player, arg2 (optional): color to commit (default: defense color)",
+        1,
+        |io, declared, s| {
+            match s.get(1) {
+                Some(color) => declared.add_with(s[0], parse_power_type(color)?),
+                None => declared.add(s[0]),
+            }
             .map_err(|e| ExecError::Other(Box::new(e)))?;
-        writeln!(io, "Adding defenders to {}", declared)?;
-        Ok(())
-    });
-    shell.set_prompt("Add defender or quit to add more attackers:".into());
+            writeln!(io, "Adding defenders to {}", declared)?;
+            Ok(())
+        },
+    );
+    let rules = rules.clone();
+    shell.new_command(
+        "odds",
+        "Show the chances of the attack succeeding, and how one more defender or attacker would change them",
+        0,
+        move |io, declared, _s| {
+            write!(io, "{}", declared.odds(&rules))?;
+            Ok(())
+        },
+    );
+    shell.set_prompt("Add defender, check the odds, or quit to add more attackers:".into());
 
     prompt(shell);
 
-    Ok(declared.finalize_defense())
+    Ok(declared.finalize_defense(rng))
 }
 
-fn add_attackers(mut declared: AddAttacker) -> Result<Attack, ExecError> {
+fn add_attackers(mut declared: AddAttacker, rules: &Rules) -> Result<Attack, ExecError> {
     println!("Adding attackers to {}", &declared);
     let mut shell = Shell::new(&mut declared);
-    shell.new_command("attacker", "Add an attacker", 1, |io, declared, s| {
-        declared
-            .add(s[0])
+    shell.new_command(
+        "attacker",
+        "Add an attacker; arg1: player, arg2 (optional): color to commit (default: attack color)",
+        1,
+        |io, declared, s| {
+            match s.get(1) {
+                Some(color) => declared.add_with(s[0], parse_power_type(color)?),
+                None => declared.add(s[0]),
+            }
             .map_err(|e| ExecError::Other(Box::new(e)))?;
-        writeln!(io, "Adding attackers to {}", declared)?;
-        Ok(())
-    });
-    shell.set_prompt("Add attacker or quit to resolve the attack:".into());
+            writeln!(io, "Adding attackers to {}", declared)?;
+            Ok(())
+        },
+    );
+    let rules = rules.clone();
+    shell.new_command(
+        "odds",
+        "Show the chances of the attack succeeding, and how one more attacker would change them",
+        0,
+        move |io, declared, _s| {
+            write!(io, "{}", declared.odds(&rules))?;
+            Ok(())
+        },
+    );
+    shell.set_prompt("Add attacker, check the odds, or quit to resolve the attack:".into());
 
     prompt(shell);
 
@@ -1,4 +1,7 @@
 extern crate rand;
 
 pub mod actions;
+pub mod calibration;
 pub mod gamestate;
+pub mod simulation;
+pub mod strategy;
@@ -1,7 +1,186 @@
 mod interactive;
 
+use std::process;
+
+use ultlib::calibration::{self, CalibrationConfig};
+use ultlib::gamestate::{log, rules::Rules};
+use ultlib::simulation::{self, SimulationConfig};
+
+const USAGE: &str = "Usage:
+  simulator [--seed <number>] [--rules <file>]
+      Play an interactive game.
+  simulator batch [--games <n>] [--teams <n>] [--players <n>] [--seed <number>]
+                  [--max-attacks <n>] [--strategy random|greedy|role-aware]
+                  [--rules <file>] [--powers independent|bag|budget:<total>|balanced]
+                  [--assignment random|balanced]
+      Play many automated games and report aggregate statistics.
+  simulator calibrate [--target <min>-<max>] [batch options]
+      Play automated games over a grid of power ranges, advantage multipliers,
+      death rules and tie rules, and report which hit the target kill rate
+      (the share of fights in which someone dies; 1/5-1/3 by default).
+  simulator replay <log file>
+      Rebuild a game from its event log and check that it plays out the same.
+
+Rules files are JSON; any rule left out keeps its default value.";
+
 fn main() {
-    interactive::run();
+    let mut args = std::env::args().skip(1).peekable();
+    if args.peek().map(String::as_str) == Some("batch") {
+        args.next();
+        run_batch(args);
+        return;
+    }
+    if args.peek().map(String::as_str) == Some("calibrate") {
+        args.next();
+        run_calibration(args);
+        return;
+    }
+    if args.peek().map(String::as_str) == Some("replay") {
+        args.next();
+        run_replay(args.next());
+        return;
+    }
+    let mut seed = None;
+    let mut rules = Rules::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => seed = Some(parse_value(&arg, args.next())),
+            "--rules" => rules = load_rules(args.next()),
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                return;
+            }
+            other => usage_error(&format!("Unrecognized argument: {}", other)),
+        }
+    }
+    if let Err(e) = interactive::run(seed, rules) {
+        eprintln!("Could not start the game: {}", e);
+        process::exit(1);
+    }
+}
+
+fn run_batch(args: impl Iterator<Item = String>) {
+    let config = parse_simulation_args(args, |arg, _| {
+        usage_error(&format!("Unrecognized argument: {}", arg))
+    });
+    println!(
+        "Simulating {} games: {} teams, {} players, {} strategy, seeds starting at {}",
+        config.games, config.teams, config.players, config.strategy, config.seed
+    );
+    match simulation::run(&config) {
+        Ok(report) => print!("{}", report),
+        Err(e) => {
+            eprintln!("Could not start games: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+fn run_calibration(args: impl Iterator<Item = String>) {
+    let mut target = Default::default();
+    let simulation = parse_simulation_args(args, |arg, value| match arg {
+        "--target" => {
+            target = value().and_then(|v| v.parse().ok()).unwrap_or_else(|| {
+                usage_error("Missing or invalid value for --target (e.g. 1/5-1/3)")
+            })
+        }
+        other => usage_error(&format!("Unrecognized argument: {}", other)),
+    });
+    let config = CalibrationConfig {
+        simulation,
+        target,
+        ..Default::default()
+    };
+    println!(
+        "Calibrating with {} games per setting: {} teams, {} players, {} strategy, seeds starting at {}",
+        config.simulation.games,
+        config.simulation.teams,
+        config.simulation.players,
+        config.simulation.strategy,
+        config.simulation.seed
+    );
+    match calibration::run(&config) {
+        Ok(report) => print!("{}", report),
+        Err(e) => {
+            eprintln!("Could not start games: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+// Handles the flags shared by `batch` and `calibrate`; any others are passed
+// to `other`, along with a way to take their value.
+fn parse_simulation_args(
+    mut args: impl Iterator<Item = String>,
+    mut other: impl FnMut(&str, &mut dyn FnMut() -> Option<String>),
+) -> SimulationConfig {
+    let mut config = SimulationConfig {
+        seed: rand::random(),
+        ..Default::default()
+    };
+    let (mut powers, mut assignment) = (None, None);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--games" => config.games = parse_value(&arg, args.next()),
+            "--teams" => config.teams = parse_value(&arg, args.next()),
+            "--players" => config.players = parse_value(&arg, args.next()),
+            "--seed" => config.seed = parse_value(&arg, args.next()),
+            "--max-attacks" => config.max_attacks = parse_value(&arg, args.next()),
+            "--strategy" => config.strategy = parse_value(&arg, args.next()),
+            "--rules" => config.rules = load_rules(args.next()),
+            "--powers" => powers = Some(parse_value(&arg, args.next())),
+            "--assignment" => assignment = Some(parse_value(&arg, args.next())),
+            _ => other(&arg, &mut || args.next()),
+        }
+    }
+    // Overrides the rules file, wherever the flags appear
+    if let Some(powers) = powers {
+        config.rules.power_distribution = powers;
+    }
+    if let Some(assignment) = assignment {
+        config.rules.team_assignment = assignment;
+    }
+    if let Err(e) = config.rules.validate() {
+        usage_error(&e.to_string());
+    }
+    config
+}
+
+fn run_replay(path: Option<String>) {
+    let path = path.unwrap_or_else(|| usage_error("Missing log file"));
+    match log::load_file(&path).and_then(|events| log::replay(&events)) {
+        Ok(game) => {
+            println!(
+                "Replayed {} events; the game matches the log.",
+                game.log().events().len()
+            );
+            print!("{}", game);
+        }
+        Err(e) => {
+            eprintln!("Could not replay {}: {}", path, e);
+            process::exit(1);
+        }
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> T {
+    match value.map(|v| v.parse()) {
+        Some(Ok(parsed)) => parsed,
+        _ => usage_error(&format!("Missing or invalid value for {}", flag)),
+    }
+}
+
+fn load_rules(path: Option<String>) -> Rules {
+    let path = path.unwrap_or_else(|| usage_error("Missing value for --rules"));
+    Rules::load_file(&path).unwrap_or_else(|e| {
+        eprintln!("Could not load rules from {}: {}", path, e);
+        process::exit(1);
+    })
+}
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    process::exit(2);
 }
 
 #[cfg(test)]
@@ -10,6 +189,7 @@ use ultlib::{actions, gamestate};
 #[test]
 fn dummy_game() -> Result<(), actions::attack::InvalidAttackErr> {
     let mut game = gamestate::builder::Setup::new_game()
+        .with_seed(0)
         .add_team_or_panic("Geats")
         .add_player_or_panic("Kyle")
         .add_player_or_panic("Laura")
@@ -24,16 +204,18 @@ fn dummy_game() -> Result<(), actions::attack::InvalidAttackErr> {
         .unwrap();
     println!("Start-of-game setup: {}", &game);
 
+    let (state, rng) = game.state_and_rng();
     let attack = actions::attack::DeclaredAttack::declare(
-        game.current_state(),
+        state,
         "Kyle",
         "Brandon",
         gamestate::power::PowerType::Red,
+        None,
     )
     .unwrap()
     .add_or_panic("Laura")
     .add_or_panic("Annabelle")
-    .finalize_defense()
+    .finalize_defense(rng)
     .add_or_panic("Suzie")
     .finalize_offense();
 
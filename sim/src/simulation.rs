@@ -0,0 +1,224 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::actions::attack::AttackOutcome;
+use crate::gamestate::active::ActiveGame;
+use crate::gamestate::builder::{Setup, StartGameErr};
+use crate::gamestate::rules::Rules;
+use crate::gamestate::teams::TName;
+use crate::gamestate::victory::{self, GameResult};
+use crate::strategy::{self, StrategyKind};
+
+#[derive(Clone, Debug)]
+pub struct SimulationConfig {
+    pub games: usize,
+    pub teams: usize,
+    pub players: usize,
+    // Game `n` is played with seed `seed + n`, so any single game can be
+    // reproduced on its own.
+    pub seed: u64,
+    // Guards against games that never end
+    pub max_attacks: usize,
+    // Used by every player
+    pub strategy: StrategyKind,
+    // Must be valid (see `Rules::validate`)
+    pub rules: Rules,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        SimulationConfig {
+            games: 100,
+            teams: 2,
+            players: 8,
+            seed: 0,
+            max_attacks: 500,
+            strategy: StrategyKind::Random,
+            rules: Default::default(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct GameSummary {
+    pub seed: u64,
+    pub attacks: usize,
+    // False if the game was cut off by `max_attacks`, or if nobody was
+    // willing to attack
+    pub finished: bool,
+    pub result: GameResult,
+}
+
+pub fn run(config: &SimulationConfig) -> Result<SimulationReport, StartGameErr> {
+    let mut report = SimulationReport {
+        rules: config.rules.clone(),
+        ..Default::default()
+    };
+    for game in 0..config.games {
+        let summary = play_game(config, config.seed.wrapping_add(game as u64))?;
+        report.record(&summary);
+    }
+    Ok(report)
+}
+
+pub fn play_game(config: &SimulationConfig, seed: u64) -> Result<GameSummary, StartGameErr> {
+    play_observed(config, seed, |_| {})
+}
+
+// Like `play_game`, but shows every attack's outcome to `observe` before it
+// is applied
+pub fn play_observed(
+    config: &SimulationConfig,
+    seed: u64,
+    mut observe: impl FnMut(&AttackOutcome),
+) -> Result<GameSummary, StartGameErr> {
+    let mut game = new_game(config, seed)?;
+    let mut strategies = config.strategy.for_everyone(game.current_state());
+    let mut attacks = 0;
+    while !game.is_over() && attacks < config.max_attacks {
+        let (state, rng) = game.state_and_rng();
+        let outcome = match strategy::automated_attack(state, &config.rules, &mut strategies, rng) {
+            Some(attack) => game.preview(attack),
+            None => break,
+        };
+        observe(&outcome);
+        game.apply_attack_outcome(outcome);
+        attacks += 1;
+    }
+    Ok(GameSummary {
+        seed,
+        attacks,
+        finished: game.is_over(),
+        result: victory::evaluate(game.current_state()),
+    })
+}
+
+fn new_game(config: &SimulationConfig, seed: u64) -> Result<ActiveGame, StartGameErr> {
+    let mut setup = Setup::new_game()
+        .with_seed(seed)
+        .with_rules(config.rules.clone());
+    for team in 1..=config.teams {
+        setup
+            .add_team(&format!("Team{}", team))
+            .expect("Generated team names are unique");
+    }
+    for player in 1..=config.players {
+        setup
+            .add_player(&format!("P{}", player))
+            .expect("Generated player names are unique");
+    }
+    setup.finalize()
+}
+
+#[derive(Debug, Default)]
+pub struct SimulationReport {
+    // The rules every game was played with
+    pub rules: Rules,
+    pub games: usize,
+    // Games that stopped before a winner was decided
+    pub unfinished: usize,
+    // Games won outright; ties are counted separately
+    pub wins: BTreeMap<TName, usize>,
+    pub ties: usize,
+    // Final honor of each team, one entry per game
+    pub honor: BTreeMap<TName, Vec<i16>>,
+    pub total_attacks: usize,
+    pub destined_survived: usize,
+}
+
+impl SimulationReport {
+    pub fn record(&mut self, game: &GameSummary) {
+        self.games += 1;
+        if !game.finished {
+            self.unfinished += 1;
+        }
+        for standing in &game.result.standings {
+            self.wins.entry(standing.team.clone()).or_default();
+            self.honor
+                .entry(standing.team.clone())
+                .or_default()
+                .push(standing.honor);
+        }
+        match game.result.winners.as_slice() {
+            [winner] => *self.wins.entry(winner.clone()).or_default() += 1,
+            _ => self.ties += 1,
+        }
+        self.total_attacks += game.attacks;
+        if game.result.all_destined_survived {
+            self.destined_survived += 1;
+        }
+    }
+
+    pub fn average_length(&self) -> f64 {
+        ratio(self.total_attacks, self.games)
+    }
+
+    pub fn destined_survival_rate(&self) -> f64 {
+        ratio(self.destined_survived, self.games)
+    }
+}
+
+fn ratio(count: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        count as f64 / total as f64
+    }
+}
+
+impl fmt::Display for SimulationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Rules:\n{}", self.rules)?;
+        writeln!(
+            f,
+            "Games played: {} ({} unfinished)",
+            self.games, self.unfinished
+        )?;
+        writeln!(
+            f,
+            "Average game length: {:.2} attacks",
+            self.average_length()
+        )?;
+        writeln!(
+            f,
+            "Destined survival rate: {:.1}%",
+            100.0 * self.destined_survival_rate()
+        )?;
+        writeln!(f, "Ties: {}", self.ties)?;
+        writeln!(
+            f,
+            "{:>12} {:>6} {:>6} {:>6} {:>6} {:>6}",
+            "Team", "Wins", "Min", "Median", "Mean", "Max"
+        )?;
+        for (team, wins) in &self.wins {
+            let mut honor = self.honor.get(team).cloned().unwrap_or_default();
+            honor.sort_unstable();
+            let mean = honor.iter().map(|&h| f64::from(h)).sum::<f64>() / honor.len().max(1) as f64;
+            writeln!(
+                f,
+                "{:>12} {:>6} {:>6} {:>6} {:>6.1} {:>6}",
+                team.0,
+                wins,
+                honor.first().copied().unwrap_or_default(),
+                honor.get(honor.len() / 2).copied().unwrap_or_default(),
+                mean,
+                honor.last().copied().unwrap_or_default(),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn simulations_are_reproducible() {
+    let config = SimulationConfig {
+        games: 5,
+        seed: 42,
+        ..Default::default()
+    };
+    let first = run(&config).unwrap();
+    let second = run(&config).unwrap();
+    assert_eq!(first.games, 5);
+    assert_eq!(first.unfinished, 0);
+    assert_eq!(format!("{:?}", first), format!("{:?}", second));
+}
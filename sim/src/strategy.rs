@@ -0,0 +1,572 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use quick_error::quick_error;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::actions::attack::{Attack, DeclaredAttack};
+use crate::gamestate::active::GameRng;
+use crate::gamestate::players::{PName, Role};
+use crate::gamestate::power::PowerType;
+use crate::gamestate::rules::Rules;
+use crate::gamestate::teams::TeamsByName;
+use crate::gamestate::view::{PlayerView, PublicPlayer};
+
+// Decides what an automated player does. Each hook is given only what that
+// player is allowed to know.
+pub trait Strategy {
+    // The player to attack, or `None` to pass up the chance to attack
+    fn choose_target(&mut self, me: &PlayerView, rng: &mut GameRng) -> Option<PName>;
+    // Only called for the targeted defender, including a protector who has
+    // taken their place; must be a color they still hold
+    fn choose_defense(&mut self, me: &PlayerView, attacker: &PName, rng: &mut GameRng)
+        -> PowerType;
+    // Only called for sworn protectors who could take the targeted defender's
+    // place
+    fn intercept(&mut self, me: &PlayerView, attack: &DeclaredAttack, rng: &mut GameRng) -> bool;
+    fn join_defense(&mut self, me: &PlayerView, attack: &DeclaredAttack, rng: &mut GameRng)
+        -> bool;
+    fn join_offense(
+        &mut self,
+        me: &PlayerView,
+        attack: &DeclaredAttack,
+        att_power: PowerType,
+        rng: &mut GameRng,
+    ) -> bool;
+}
+
+// Players without a strategy are never automated.
+pub type Strategies = BTreeMap<PName, Box<dyn Strategy>>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StrategyKind {
+    Random,
+    GreedyHonor,
+    RoleAware,
+}
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum StrategyErr {
+        UnknownStrategy(name: String) {
+            display("Unknown strategy '{}' (expected random, greedy, or role-aware)", name)
+        }
+    }
+}
+
+impl FromStr for StrategyKind {
+    type Err = StrategyErr;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "random" => Ok(StrategyKind::Random),
+            "greedy" | "greedy-honor" => Ok(StrategyKind::GreedyHonor),
+            "role-aware" | "roles" => Ok(StrategyKind::RoleAware),
+            _ => Err(StrategyErr::UnknownStrategy(s.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for StrategyKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StrategyKind::Random => write!(f, "random"),
+            StrategyKind::GreedyHonor => write!(f, "greedy"),
+            StrategyKind::RoleAware => write!(f, "role-aware"),
+        }
+    }
+}
+
+impl StrategyKind {
+    pub fn build(self) -> Box<dyn Strategy> {
+        match self {
+            StrategyKind::Random => Box::new(RandomStrategy),
+            StrategyKind::GreedyHonor => Box::new(GreedyHonor),
+            StrategyKind::RoleAware => Box::new(RoleAware),
+        }
+    }
+
+    // The same kind of strategy for every player in the game
+    pub fn for_everyone(self, state: &TeamsByName) -> Strategies {
+        state
+            .players()
+            .map(|p| (p.name.clone(), self.build()))
+            .collect()
+    }
+}
+
+// Plays out a single attack, with every decision made by the players'
+// strategies. Players are offered the chance to attack in a random order, and
+// the first to choose a target becomes the attacker. Returns `None` if nobody
+// wants to attack.
+pub fn automated_attack(
+    state: &TeamsByName,
+    rules: &Rules,
+    strategies: &mut Strategies,
+    rng: &mut GameRng,
+) -> Option<Attack> {
+    let mut living = state
+        .players()
+        .filter(|p| p.is_alive())
+        .map(|p| p.name.clone())
+        .collect::<Vec<_>>();
+    living.shuffle(rng);
+
+    let (attacker, target) = living.iter().find_map(|name| {
+        let strategy = strategies.get_mut(name)?;
+        let target = strategy.choose_target(&PlayerView::new(state, rules, name), rng)?;
+        Some((name.clone(), target))
+    })?;
+
+    let defender = PlayerView::new(state, rules, &target);
+    let chosen = match strategies.get_mut(&target) {
+        Some(strategy) => strategy.choose_defense(&defender, &attacker, rng),
+        None => *available_powers(defender.me()).first()?,
+    };
+    let def_power = if defender.me().has_power(chosen) {
+        chosen
+    } else {
+        *available_powers(defender.me()).first()?
+    };
+
+    let mut defense =
+        DeclaredAttack::declare(state, attacker.as_str(), target.as_str(), def_power, None).ok()?;
+    // Any sworn protector may take the defender's place; the first to accept
+    // does.
+    for protector in defense.sworn_protectors() {
+        if let Some(strategy) = strategies.get_mut(&protector) {
+            let me = PlayerView::new(state, rules, &protector);
+            if strategy.intercept(&me, &defense.attack, rng) {
+                defense
+                    .intercept(protector.as_str())
+                    .expect("Sworn protectors can intercept");
+                // They may defend with another of their colors.
+                let chosen = strategy.choose_defense(&me, &attacker, rng);
+                let _ = defense.choose_def_power(chosen);
+                break;
+            }
+        }
+    }
+    for name in &living {
+        if defense.attack.is_combatant(name) {
+            continue;
+        }
+        if let Some(strategy) = strategies.get_mut(name) {
+            if strategy.join_defense(&PlayerView::new(state, rules, name), &defense.attack, rng) {
+                // Ineligible assists (e.g. missing the color) simply don't join.
+                let _ = defense.add(name.as_str());
+            }
+        }
+    }
+
+    let mut offense = defense.finalize_defense(rng);
+    let att_power = offense.att_power();
+    for name in &living {
+        if offense.attack.is_combatant(name) {
+            continue;
+        }
+        if let Some(strategy) = strategies.get_mut(name) {
+            let me = PlayerView::new(state, rules, name);
+            if strategy.join_offense(&me, &offense.attack, att_power, rng) {
+                let _ = offense.add(name.as_str());
+            }
+        }
+    }
+    Some(offense.finalize_offense())
+}
+
+fn available_powers(player: PublicPlayer) -> Vec<PowerType> {
+    PowerType::ALL
+        .iter()
+        .copied()
+        .filter(|&ptype| player.has_power(ptype))
+        .collect()
+}
+
+// Allies are left alone, as attacking one would end the alliance.
+fn living_opponents<'a>(me: &'a PlayerView) -> impl Iterator<Item = PublicPlayer<'a>> {
+    me.players()
+        .filter(move |p| p.is_alive() && !me.is_friendly(p.team()))
+}
+
+// Each combatant fights with the color they committed.
+fn total_strength<'a>(
+    me: &PlayerView,
+    combatants: impl Iterator<Item = (&'a PName, PowerType)>,
+) -> i16 {
+    combatants
+        .map(|(name, ptype)| i16::from(me.player(name).strength(ptype)))
+        .sum()
+}
+
+fn defense_strength(me: &PlayerView, attack: &DeclaredAttack) -> i16 {
+    total_strength(
+        me,
+        std::iter::once((attack.targeted_defender(), attack.def_power()))
+            .chain(attack.defender_assists().iter().map(|(n, &p)| (n, p))),
+    )
+}
+
+// Net of both sides' color advantages, so it can be compared directly with
+// `defense_strength`
+fn offense_strength(me: &PlayerView, attack: &DeclaredAttack, att_power: PowerType) -> i16 {
+    let attackers = std::iter::once((attack.initial_attacker(), att_power))
+        .chain(attack.attacker_assists().iter().map(|(n, &p)| (n, p)))
+        .collect::<Vec<_>>();
+    let attacking = attackers.iter().map(|&(_, p)| p).collect::<Vec<_>>();
+    let defending = std::iter::once(attack.def_power())
+        .chain(attack.defender_assists().values().copied())
+        .collect::<Vec<_>>();
+    let bonus = me
+        .rules()
+        .side_advantage(attacking.iter().copied(), &defending)
+        - me.rules()
+            .side_advantage(defending.iter().copied(), &attacking);
+    total_strength(me, attackers.into_iter()) + bonus
+}
+
+// Every decision is made at random: the target, the defense color, and
+// whether to join a teammate's (or ally's) fight.
+pub struct RandomStrategy;
+
+// Chance that a random player joins a teammate's fight as an assist
+const JOIN_PROBABILITY: f64 = 1.0 / 3.0;
+
+impl Strategy for RandomStrategy {
+    fn choose_target(&mut self, me: &PlayerView, rng: &mut GameRng) -> Option<PName> {
+        living_opponents(me)
+            .collect::<Vec<_>>()
+            .choose(rng)
+            .map(|p| p.name().clone())
+    }
+
+    fn choose_defense(&mut self, me: &PlayerView, _: &PName, rng: &mut GameRng) -> PowerType {
+        *available_powers(me.me())
+            .choose(rng)
+            .expect("A living player has at least one token")
+    }
+
+    fn intercept(&mut self, _: &PlayerView, _: &DeclaredAttack, rng: &mut GameRng) -> bool {
+        rng.gen_bool(JOIN_PROBABILITY)
+    }
+
+    fn join_defense(
+        &mut self,
+        me: &PlayerView,
+        attack: &DeclaredAttack,
+        rng: &mut GameRng,
+    ) -> bool {
+        me.is_friendly(&attack.def_team().0) && rng.gen_bool(JOIN_PROBABILITY)
+    }
+
+    fn join_offense(
+        &mut self,
+        me: &PlayerView,
+        attack: &DeclaredAttack,
+        _: PowerType,
+        rng: &mut GameRng,
+    ) -> bool {
+        me.is_friendly(&attack.att_team().0) && rng.gen_bool(JOIN_PROBABILITY)
+    }
+}
+
+// Goes after the most honor it can reasonably expect to win, and only spends
+// tokens as an assist when doing so turns a loss into a win.
+pub struct GreedyHonor;
+
+impl GreedyHonor {
+    // Honor expected from attacking `target` alone, if they defend with their
+    // strongest token and the attacking color is rolled at random
+    fn expected_honor(rules: &Rules, attacker: PublicPlayer, target: PublicPlayer) -> f64 {
+        let def_power = match available_powers(target)
+            .into_iter()
+            .max_by_key(|&ptype| target.strength(ptype))
+        {
+            Some(ptype) => ptype,
+            None => return 0.0,
+        };
+        let defense = i16::from(target.strength(def_power));
+        let honor = PowerType::ALL
+            .iter()
+            .map(|&att_power| {
+                let attack = i16::from(attacker.strength(att_power));
+                if attack + rules.advantage(att_power, def_power) > defense {
+                    f64::from(rules.honor.honor_won(attack, defense))
+                } else {
+                    0.0
+                }
+            })
+            .sum::<f64>();
+        honor / PowerType::ALL.len() as f64
+    }
+
+    fn best_target<'a>(
+        me: &'a PlayerView,
+        exclude: impl Fn(&PName) -> bool,
+    ) -> Option<PublicPlayer<'a>> {
+        living_opponents(me)
+            .filter(|target| !exclude(target.name()))
+            .max_by(|a, b| {
+                Self::expected_honor(me.rules(), me.me(), *a)
+                    .partial_cmp(&Self::expected_honor(me.rules(), me.me(), *b))
+                    .expect("Expected honor is never NaN")
+            })
+    }
+
+    fn safest_defense(me: &PlayerView, attacker: &PName) -> PowerType {
+        // Nobody has committed to assisting yet.
+        me.rank_defenses(attacker)
+            .first()
+            .expect("A living player has at least one token")
+            .power
+    }
+
+    // True if the defense might lose without this player, but can't with them
+    fn defense_needs_me(me: &PlayerView, attack: &DeclaredAttack) -> bool {
+        let defense = defense_strength(me, attack);
+        let threat = PowerType::ALL
+            .iter()
+            .map(|&att_power| offense_strength(me, attack, att_power))
+            .max()
+            .unwrap_or_default();
+        let mine = i16::from(me.me().strength(attack.def_power()));
+        defense < threat && defense + mine >= threat
+    }
+
+    // True if the offense would lose without this player, but wins with them
+    fn offense_needs_me(me: &PlayerView, attack: &DeclaredAttack, att_power: PowerType) -> bool {
+        let offense = offense_strength(me, attack, att_power);
+        let defense = defense_strength(me, attack);
+        let mine = i16::from(me.me().strength(att_power));
+        offense <= defense && offense + mine > defense
+    }
+}
+
+impl Strategy for GreedyHonor {
+    fn choose_target(&mut self, me: &PlayerView, _: &mut GameRng) -> Option<PName> {
+        Self::best_target(me, |_| false).map(|p| p.name().clone())
+    }
+
+    fn choose_defense(&mut self, me: &PlayerView, attacker: &PName, _: &mut GameRng) -> PowerType {
+        Self::safest_defense(me, attacker)
+    }
+
+    // Only when it would join the defense anyway
+    fn intercept(&mut self, me: &PlayerView, attack: &DeclaredAttack, rng: &mut GameRng) -> bool {
+        self.join_defense(me, attack, rng)
+    }
+
+    fn join_defense(&mut self, me: &PlayerView, attack: &DeclaredAttack, _: &mut GameRng) -> bool {
+        me.is_friendly(&attack.def_team().0) && Self::defense_needs_me(me, attack)
+    }
+
+    fn join_offense(
+        &mut self,
+        me: &PlayerView,
+        attack: &DeclaredAttack,
+        att_power: PowerType,
+        _: &mut GameRng,
+    ) -> bool {
+        me.is_friendly(&attack.att_team().0) && Self::offense_needs_me(me, attack, att_power)
+    }
+}
+
+// Plays like `GreedyHonor`, except where its secret role or ward gives it a
+// different goal:
+// * A prophet only attacks its targets once nobody else is left to attack, and
+//   defends them whatever its team.
+// * A destined player avoids every fight it can, only attacking once the rest
+//   of its team is dead.
+// * A traitor picks fights it will lose and assists the other team.
+// * Anyone else takes their ward's place when it is attacked, or else joins
+//   its defense.
+pub struct RoleAware;
+
+impl RoleAware {
+    fn has_living_teammates(me: &PlayerView) -> bool {
+        me.players()
+            .any(|p| p.is_alive() && p.team() == me.team() && p.name() != me.name())
+    }
+
+    fn is_prophet_of(me: &PlayerView, name: &PName) -> bool {
+        matches!(me.role(), Some(Role::Prophet { targets }) if targets.iter().any(|t| *name == t.as_str()))
+    }
+}
+
+impl Strategy for RoleAware {
+    fn choose_target(&mut self, me: &PlayerView, rng: &mut GameRng) -> Option<PName> {
+        match me.role() {
+            Some(Role::Destined) if Self::has_living_teammates(me) => None,
+            Some(Role::Traitor) => living_opponents(me)
+                .max_by_key(|p| {
+                    PowerType::ALL
+                        .iter()
+                        .map(|&ptype| i16::from(p.strength(ptype)))
+                        .sum::<i16>()
+                })
+                .map(|p| p.name().clone()),
+            Some(Role::Prophet { .. }) => {
+                GreedyHonor::best_target(me, |name| Self::is_prophet_of(me, name))
+                    .or_else(|| GreedyHonor::best_target(me, |_| false))
+                    .map(|p| p.name().clone())
+            }
+            _ => GreedyHonor.choose_target(me, rng),
+        }
+    }
+
+    fn choose_defense(
+        &mut self,
+        me: &PlayerView,
+        attacker: &PName,
+        rng: &mut GameRng,
+    ) -> PowerType {
+        match me.role() {
+            // Throw the fight, giving honor to the other team
+            Some(Role::Traitor) => available_powers(me.me())
+                .into_iter()
+                .min_by_key(|&ptype| me.me().strength(ptype))
+                .expect("A living player has at least one token"),
+            _ => GreedyHonor.choose_defense(me, attacker, rng),
+        }
+    }
+
+    fn intercept(&mut self, me: &PlayerView, _: &DeclaredAttack, _: &mut GameRng) -> bool {
+        !matches!(me.role(), Some(Role::Destined))
+    }
+
+    fn join_defense(
+        &mut self,
+        me: &PlayerView,
+        attack: &DeclaredAttack,
+        rng: &mut GameRng,
+    ) -> bool {
+        let defender = attack.targeted_defender();
+        match me.role() {
+            Some(Role::Destined) => false,
+            _ if Self::is_prophet_of(me, defender) || me.ward() == Some(defender) => true,
+            Some(Role::Traitor) => {
+                me.team() != attack.def_team().0 && me.team() == attack.att_team().0
+            }
+            _ => GreedyHonor.join_defense(me, attack, rng),
+        }
+    }
+
+    fn join_offense(
+        &mut self,
+        me: &PlayerView,
+        attack: &DeclaredAttack,
+        att_power: PowerType,
+        rng: &mut GameRng,
+    ) -> bool {
+        let defender = attack.targeted_defender();
+        match me.role() {
+            Some(Role::Destined) => false,
+            _ if Self::is_prophet_of(me, defender) || me.ward() == Some(defender) => false,
+            Some(Role::Traitor) => {
+                me.team() == attack.def_team().0 && me.team() != attack.att_team().0
+            }
+            _ => GreedyHonor.join_offense(me, attack, att_power, rng),
+        }
+    }
+}
+
+#[test]
+fn every_strategy_finishes_games() {
+    use crate::simulation::{self, SimulationConfig};
+
+    // Each game must end within the simulator's limit on attacks, with nobody
+    // refusing to attack while opponents remain.
+    for strategy in [
+        StrategyKind::Random,
+        StrategyKind::GreedyHonor,
+        StrategyKind::RoleAware,
+    ] {
+        let config = SimulationConfig {
+            players: 10,
+            strategy,
+            ..Default::default()
+        };
+        for seed in 0..20 {
+            let summary = simulation::play_game(&config, seed).unwrap();
+            assert!(
+                summary.finished,
+                "{} game with seed {} stopped after {} attacks",
+                strategy, seed, summary.attacks
+            );
+        }
+    }
+}
+
+#[test]
+fn protectors_choose_whether_to_intercept() {
+    use crate::actions::attack::AttackStep;
+    use crate::gamestate::log::GameEvent;
+
+    // Attacks the given target, and otherwise only intercepts if told to
+    struct Scripted {
+        target: Option<PName>,
+        intercepts: bool,
+    }
+
+    impl Strategy for Scripted {
+        fn choose_target(&mut self, _: &PlayerView, _: &mut GameRng) -> Option<PName> {
+            self.target.clone()
+        }
+
+        fn choose_defense(&mut self, me: &PlayerView, _: &PName, _: &mut GameRng) -> PowerType {
+            available_powers(me.me())[0]
+        }
+
+        fn intercept(&mut self, _: &PlayerView, _: &DeclaredAttack, _: &mut GameRng) -> bool {
+            self.intercepts
+        }
+
+        fn join_defense(&mut self, _: &PlayerView, _: &DeclaredAttack, _: &mut GameRng) -> bool {
+            false
+        }
+
+        fn join_offense(
+            &mut self,
+            _: &PlayerView,
+            _: &DeclaredAttack,
+            _: PowerType,
+            _: &mut GameRng,
+        ) -> bool {
+            false
+        }
+    }
+
+    for intercepts in [false, true] {
+        let mut game = crate::gamestate::builder::test_game(0, Default::default());
+        let (target, _) = game.player_by_name("Kyle").unwrap();
+        let protector = game
+            .players()
+            .find(|p| p.ward() == Some(&target))
+            .unwrap()
+            .name
+            .clone();
+        let attacker = game
+            .players()
+            .find(|p| p.name != target && p.name != protector && p.team != "Geats")
+            .unwrap()
+            .name
+            .clone();
+        let mut strategies = Strategies::new();
+        for name in [&target, &protector, &attacker] {
+            let scripted = Scripted {
+                target: Some(target.clone()).filter(|_| *name == attacker),
+                intercepts,
+            };
+            strategies.insert(name.clone(), Box::new(scripted));
+        }
+        let rules = game.rules().clone();
+        let (state, rng) = game.state_and_rng();
+        let attack = automated_attack(state, &rules, &mut strategies, rng).unwrap();
+        game.apply_attack(attack);
+        let intercepted = game.log().events().iter().any(|event| {
+            matches!(event, GameEvent::Attack(AttackStep::Intercepted { protector: p }) if *p == protector)
+        });
+        assert_eq!(intercepted, intercepts);
+    }
+}